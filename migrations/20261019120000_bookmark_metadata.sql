@@ -0,0 +1,4 @@
+-- Optional display metadata for server bookmarks
+ALTER TABLE `server_bookmarks`
+    ADD COLUMN `description` TEXT,
+    ADD COLUMN `emoji` VARCHAR(64);
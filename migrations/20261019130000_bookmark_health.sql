@@ -0,0 +1,3 @@
+-- When each bookmarked server was last listed by the tracker
+ALTER TABLE `server_bookmarks`
+    ADD COLUMN `last_seen` DATETIME;
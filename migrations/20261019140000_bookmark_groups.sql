@@ -0,0 +1,14 @@
+-- Named groups of server bookmarks
+CREATE TABLE `bookmark_groups` (
+    `id` INT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    `guild_id` BIGINT UNSIGNED NOT NULL,
+    `group_name` TEXT NOT NULL
+);
+
+CREATE TABLE `bookmark_group_members` (
+    `group_id` INT NOT NULL,
+    `bookmark_id` INT NOT NULL,
+    PRIMARY KEY (`group_id`, `bookmark_id`),
+    FOREIGN KEY (`group_id`) REFERENCES `bookmark_groups` (`id`) ON DELETE CASCADE,
+    FOREIGN KEY (`bookmark_id`) REFERENCES `server_bookmarks` (`id`) ON DELETE CASCADE
+);
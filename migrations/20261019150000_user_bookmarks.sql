@@ -0,0 +1,8 @@
+-- Server bookmarks belonging to a single Discord user
+CREATE TABLE `user_bookmarks` (
+    `id` INT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    `user_id` BIGINT UNSIGNED NOT NULL,
+    `bookmark_name` TEXT NOT NULL,
+    `host` TEXT NOT NULL,
+    `port` INT UNSIGNED NOT NULL
+);
@@ -0,0 +1,17 @@
+-- Self-updating server status messages, one per guild
+CREATE TABLE `live_status` (
+    `guild_id` BIGINT UNSIGNED PRIMARY KEY NOT NULL,
+    `channel_id` BIGINT UNSIGNED NOT NULL,
+    `message_id` BIGINT UNSIGNED NOT NULL,
+    `update_interval` INT UNSIGNED NOT NULL,
+    `all_bookmarks` BOOLEAN NOT NULL
+);
+
+-- Bookmarks shown when a status message doesn't show all of them
+CREATE TABLE `live_status_bookmarks` (
+    `guild_id` BIGINT UNSIGNED NOT NULL,
+    `bookmark_id` INT NOT NULL,
+    PRIMARY KEY (`guild_id`, `bookmark_id`),
+    FOREIGN KEY (`guild_id`) REFERENCES `live_status` (`guild_id`) ON DELETE CASCADE,
+    FOREIGN KEY (`bookmark_id`) REFERENCES `server_bookmarks` (`id`) ON DELETE CASCADE
+);
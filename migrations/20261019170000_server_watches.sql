@@ -0,0 +1,12 @@
+-- Alerts posted when a bookmarked server reaches a number of players
+CREATE TABLE `server_watches` (
+    `guild_id` BIGINT UNSIGNED NOT NULL,
+    `bookmark_id` INT NOT NULL,
+    `channel_id` BIGINT UNSIGNED NOT NULL,
+    `min_players` INT UNSIGNED NOT NULL,
+    `role_id` BIGINT UNSIGNED,
+    `triggered` BOOLEAN NOT NULL DEFAULT FALSE,
+    `last_alert` DATETIME,
+    PRIMARY KEY (`guild_id`, `bookmark_id`),
+    FOREIGN KEY (`bookmark_id`) REFERENCES `server_bookmarks` (`id`) ON DELETE CASCADE
+);
@@ -0,0 +1,10 @@
+-- Alerts sent when a player shows up on any server, to a guild channel or by DM
+CREATE TABLE `player_watches` (
+    `id` INT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    `user_id` BIGINT UNSIGNED NOT NULL,
+    `guild_id` BIGINT UNSIGNED,
+    `channel_id` BIGINT UNSIGNED,
+    `player_name` TEXT NOT NULL,
+    -- Server the last alert was sent for, as host:port, so restarts don't alert again
+    `notified_server` TEXT
+);
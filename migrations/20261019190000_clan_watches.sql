@@ -0,0 +1,11 @@
+-- Alerts posted when several players wearing a clantag are on one server
+CREATE TABLE `clan_watches` (
+    `guild_id` BIGINT UNSIGNED NOT NULL,
+    `clan_tag` VARCHAR(32) NOT NULL,
+    `channel_id` BIGINT UNSIGNED NOT NULL,
+    `min_members` INT UNSIGNED NOT NULL,
+    `members_only` BOOLEAN NOT NULL DEFAULT FALSE,
+    -- Server the last alert was sent for, as host:port, so restarts don't alert again
+    `notified_server` TEXT,
+    PRIMARY KEY (`guild_id`, `clan_tag`)
+);
@@ -0,0 +1,7 @@
+-- Channels that get the final scoreboard when a game ends on a bookmarked server
+CREATE TABLE `match_summaries` (
+    `bookmark_id` INT PRIMARY KEY NOT NULL,
+    `channel_id` BIGINT UNSIGNED NOT NULL,
+    `min_players` INT UNSIGNED NOT NULL,
+    FOREIGN KEY (`bookmark_id`) REFERENCES `server_bookmarks` (`id`) ON DELETE CASCADE
+);
@@ -0,0 +1,21 @@
+-- Alerts sent when a game on a map and/or mode starts, to a guild channel or by DM
+CREATE TABLE `map_subscriptions` (
+    `id` INT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    `user_id` BIGINT UNSIGNED NOT NULL,
+    `guild_id` BIGINT UNSIGNED,
+    `channel_id` BIGINT UNSIGNED,
+    `map_name` VARCHAR(64),
+    `game_mode` VARCHAR(32),
+    -- Only servers at this address when set, taken from a bookmark
+    `host` TEXT,
+    `port` INT UNSIGNED,
+    `min_players` INT UNSIGNED NOT NULL
+);
+
+-- Servers already alerted about for the game they're running, as host:port
+CREATE TABLE `map_subscription_games` (
+    `subscription_id` INT NOT NULL,
+    `server` VARCHAR(128) NOT NULL,
+    PRIMARY KEY (`subscription_id`, `server`),
+    FOREIGN KEY (`subscription_id`) REFERENCES `map_subscriptions` (`id`) ON DELETE CASCADE
+);
@@ -0,0 +1,18 @@
+-- Channel told when bookmarked servers go down or come back
+ALTER TABLE `guild_settings` ADD COLUMN `availability_channel` BIGINT UNSIGNED;
+
+-- When each bookmark started being monitored, so uptime only counts time it was watched
+CREATE TABLE `bookmark_monitoring` (
+    `bookmark_id` INT PRIMARY KEY NOT NULL,
+    `monitored_since` DATETIME NOT NULL,
+    FOREIGN KEY (`bookmark_id`) REFERENCES `server_bookmarks` (`id`) ON DELETE CASCADE
+);
+
+-- Periods a bookmarked server was unreachable, still ongoing while ended_at is NULL
+CREATE TABLE `server_outages` (
+    `id` INT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    `bookmark_id` INT NOT NULL,
+    `started_at` DATETIME NOT NULL,
+    `ended_at` DATETIME,
+    FOREIGN KEY (`bookmark_id`) REFERENCES `server_bookmarks` (`id`) ON DELETE CASCADE
+);
@@ -0,0 +1,33 @@
+-- Alert scheduling for each guild. Quiet hours are whole hours in the guild's own time,
+-- digests hold channel alerts for digest_minutes and post them as one message.
+ALTER TABLE `guild_settings`
+    ADD COLUMN `utc_offset` INT NOT NULL DEFAULT 0,
+    ADD COLUMN `quiet_start` TINYINT UNSIGNED,
+    ADD COLUMN `quiet_end` TINYINT UNSIGNED,
+    ADD COLUMN `digest_minutes` INT UNSIGNED NOT NULL DEFAULT 0;
+
+-- Alerts waiting to be delivered, with the message as Discord's JSON payload
+CREATE TABLE `notification_queue` (
+    `id` INT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    `target_kind` VARCHAR(16) NOT NULL,
+    `target_id` BIGINT UNSIGNED NOT NULL,
+    `guild_id` BIGINT UNSIGNED,
+    `summary` TEXT NOT NULL,
+    `payload` MEDIUMTEXT NOT NULL,
+    `queued_at` DATETIME NOT NULL
+);
+
+-- Recently alerted events, so the same one isn't sent to a target twice
+CREATE TABLE `notification_keys` (
+    `dedup_key` VARCHAR(255) PRIMARY KEY NOT NULL,
+    `created_at` DATETIME NOT NULL
+);
+
+-- Delivered messages, counted for rate limits
+CREATE TABLE `notification_log` (
+    `id` INT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    `target_kind` VARCHAR(16) NOT NULL,
+    `target_id` BIGINT UNSIGNED NOT NULL,
+    `sent_at` DATETIME NOT NULL,
+    INDEX (`target_kind`, `target_id`, `sent_at`)
+);
@@ -0,0 +1,4 @@
+-- When the health check last looked for each bookmarked server, so bookmarks it hasn't
+-- checked yet aren't flagged as never seen
+ALTER TABLE `server_bookmarks`
+    ADD COLUMN `last_checked` DATETIME;
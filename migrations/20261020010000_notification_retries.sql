@@ -0,0 +1,4 @@
+-- Failed deliveries are retried a few times, waiting longer after each attempt
+ALTER TABLE `notification_queue`
+    ADD COLUMN `attempts` INT UNSIGNED NOT NULL DEFAULT 0,
+    ADD COLUMN `next_attempt` DATETIME;
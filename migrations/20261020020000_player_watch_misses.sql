@@ -0,0 +1,3 @@
+-- Polls in a row a watched player wasn't seen, so a dropped connection doesn't alert twice
+ALTER TABLE `player_watches`
+    ADD COLUMN `missed_polls` INT UNSIGNED NOT NULL DEFAULT 0;
@@ -1,4 +1,5 @@
 use crate::{Context, Error};
+use crate::data::{format_utc_offset, parse_utc_offset};
 use poise::serenity_prelude as serenity;
 
 // -- Handling role requirements for information commands.
@@ -34,6 +35,124 @@ pub async fn setrole(
     Ok(())
 }
 
+/// Set a channel to be told when bookmarked servers go down or come back
+#[poise::command(
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn setalertchannel(
+    ctx: Context<'_>,
+    #[description = "Channel for server availability alerts. Leave empty to turn them off."]
+    #[channel_types("Text", "News")] channel: Option<serenity::GuildChannel>
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let channel_id = channel.as_ref().map(|channel| channel.id.get());
+
+    sqlx::query!("UPDATE guild_settings SET availability_channel = ? WHERE guild_id = ?", channel_id, guild_id)
+        .execute(&ctx.data().database)
+        .await
+        .unwrap();
+
+    match channel {
+        Some(channel) => ctx.say(format!("{}, alerts for bookmarked servers going down will be posted in {}!", ctx.author(), channel)).await?,
+        None => ctx.say(format!("{}, server availability alerts are turned off!", ctx.author())).await?
+    };
+
+    Ok(())
+}
+
+/// Set the timezone quiet hours are in
+#[poise::command(
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn settimezone(
+    ctx: Context<'_>,
+    #[description = "Offset from UTC, like +2 or -5:30"] utc_offset: String
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let offset = parse_utc_offset(&utc_offset)?;
+
+    sqlx::query!("UPDATE guild_settings SET utc_offset = ? WHERE guild_id = ?", offset, guild_id)
+        .execute(&ctx.data().database)
+        .await
+        .unwrap();
+
+    ctx.say(format!("{}, this server's timezone is now {}!", ctx.author(), format_utc_offset(offset))).await?;
+
+    Ok(())
+}
+
+/// Hold alerts in channels during set hours, then post them together
+#[poise::command(
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn setquiethours(
+    ctx: Context<'_>,
+    #[description = "Hour quiet hours start, 0-23. Leave empty to turn them off."]
+    #[max = 23] start: Option<u8>,
+    #[description = "Hour quiet hours end, 0-23"]
+    #[max = 23] end: Option<u8>
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+
+    let (start, end) = match (start, end) {
+        (Some(start), Some(end)) if start == end => return Err("Quiet hours can't start and end at the same hour!".into()),
+        (Some(start), Some(end)) => (Some(start), Some(end)),
+        (None, None) => (None, None),
+        _ => return Err("Give both a start and an end hour, or neither to turn quiet hours off!".into())
+    };
+
+    sqlx::query!("UPDATE guild_settings SET quiet_start = ?, quiet_end = ? WHERE guild_id = ?", start, end, guild_id)
+        .execute(&ctx.data().database)
+        .await
+        .unwrap();
+
+    let offset = sqlx::query!("SELECT utc_offset FROM guild_settings WHERE guild_id = ?", guild_id)
+        .fetch_one(&ctx.data().database)
+        .await
+        .unwrap()
+        .utc_offset;
+
+    match (start, end) {
+        (Some(start), Some(end)) => ctx.say(format!("{}, alerts will be held from {:02}:00 to {:02}:00 ({})!", ctx.author(), start, end, format_utc_offset(offset))).await?,
+        _ => ctx.say(format!("{}, quiet hours are turned off!", ctx.author())).await?
+    };
+
+    Ok(())
+}
+
+/// Collect alerts in channels for a while and post them as one message
+#[poise::command(
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn setdigest(
+    ctx: Context<'_>,
+    #[description = "Minutes to collect alerts for. Leave empty to post them right away."]
+    #[min = 1]
+    #[max = 1440] minutes: Option<u32>
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+
+    sqlx::query!("UPDATE guild_settings SET digest_minutes = ? WHERE guild_id = ?", minutes.unwrap_or(0), guild_id)
+        .execute(&ctx.data().database)
+        .await
+        .unwrap();
+
+    match minutes {
+        Some(minutes) => ctx.say(format!("{}, alerts will be collected for {} minutes and posted together!", ctx.author(), minutes)).await?,
+        None => ctx.say(format!("{}, alerts will be posted right away!", ctx.author())).await?
+    };
+
+    Ok(())
+}
+
 // Command check for required roles
 pub async fn info_role(ctx: Context<'_>) -> Result<bool, Error> {
     // Pull data and validate
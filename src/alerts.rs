@@ -0,0 +1,74 @@
+use crate::{Context, Error};
+use crate::poller::{next_snapshot, SnapshotBus};
+use crate::{subscription, summary, watch};
+use poise::serenity_prelude as serenity;
+use std::sync::Arc;
+
+// Check every kind of alert against each new snapshot
+pub async fn run(database: sqlx::MySqlPool, client: reqwest::Client, snapshots: Arc<SnapshotBus>) {
+    let mut receiver = snapshots.subscribe();
+    let mut clan_cache = watch::ClanCache::new();
+    let mut seen_subscriptions = std::collections::HashSet::new();
+
+    while let Some(snapshot) = next_snapshot(&mut receiver).await {
+        watch::check_server_watches(&database, &snapshot).await;
+        watch::check_player_watches(&database, &snapshot).await;
+        watch::check_clan_watches(&database, &client, &mut clan_cache, &snapshot).await;
+        summary::check_match_summaries(&database, &snapshot).await;
+        subscription::check_map_subscriptions(&database, &snapshot, &mut seen_subscriptions).await;
+    }
+}
+
+// Where an alert is sent
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlertTarget {
+    Channel(u64),
+    User(u64),
+}
+
+impl AlertTarget {
+    // Guild channel if one is set, otherwise the user's DMs
+    pub fn new(channel_id: Option<u64>, user_id: u64) -> Self {
+        match channel_id {
+            Some(channel_id) => AlertTarget::Channel(channel_id),
+            None => AlertTarget::User(user_id),
+        }
+    }
+
+    // Kind and ID the target is stored as
+    pub fn parts(self) -> (&'static str, u64) {
+        match self {
+            AlertTarget::Channel(id) => ("channel", id),
+            AlertTarget::User(id) => ("user", id),
+        }
+    }
+
+    pub fn from_parts(kind: &str, id: u64) -> Option<Self> {
+        match kind {
+            "channel" => Some(AlertTarget::Channel(id)),
+            "user" => Some(AlertTarget::User(id)),
+            _ => None
+        }
+    }
+}
+
+// Guild channels need someone who can manage channels, DMs are open to everyone
+pub async fn alert_channel(ctx: Context<'_>, channel: Option<&serenity::GuildChannel>) -> Result<Option<u64>, Error> {
+    let channel = match channel {
+        Some(channel) => channel,
+        None => return Ok(None)
+    };
+
+    if !can_manage_channels(ctx).await {
+        return Err("You need the Manage Channels permission to post alerts in a channel!".into());
+    }
+
+    Ok(Some(channel.id.get()))
+}
+
+pub async fn can_manage_channels(ctx: Context<'_>) -> bool {
+    match ctx.author_member().await {
+        Some(member) => member.permissions.is_some_and(|permissions| permissions.manage_channels()),
+        None => false
+    }
+}
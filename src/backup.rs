@@ -0,0 +1,454 @@
+use crate::{Context, Error};
+use crate::data::{format_utc_offset, BookmarkExport, BookmarkGroup, ExportedAlertSettings, ExportedBookmark, ExportedGroup, ExportedSettings, ServerBookmark, BOOKMARK_EXPORT_VERSION};
+use crate::group::group_bookmarks;
+use poise::serenity_prelude as serenity;
+use std::collections::{HashMap, HashSet};
+
+// Largest bookmark file /bkimport will download
+const MAX_IMPORT_SIZE: u32 = 1024 * 1024;
+
+#[derive(Clone, Copy, PartialEq, poise::ChoiceParameter)]
+pub enum ImportMode {
+    #[name = "Merge: keep existing bookmarks with the same name"]
+    Merge,
+    #[name = "Replace: overwrite existing bookmarks with the same name"]
+    Replace,
+}
+
+/// Export the guild's bookmarks, groups and settings as a JSON file
+#[poise::command(
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS",
+    guild_only
+)]
+pub async fn bkexport(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+
+    let server_bookmarks: Vec<ServerBookmark> = sqlx::query_as!(ServerBookmark, "SELECT * FROM server_bookmarks WHERE guild_id = ?", guild_id)
+        .fetch_all(&ctx.data().database)
+        .await
+        .unwrap();
+
+    let groups: Vec<BookmarkGroup> = sqlx::query_as!(BookmarkGroup, "SELECT * FROM bookmark_groups WHERE guild_id = ?", guild_id)
+        .fetch_all(&ctx.data().database)
+        .await
+        .unwrap();
+
+    let settings = sqlx::query!("SELECT infocmds_required_role, availability_channel, utc_offset, quiet_start, quiet_end, digest_minutes FROM guild_settings WHERE guild_id = ?", guild_id)
+        .fetch_optional(&ctx.data().database)
+        .await
+        .unwrap();
+
+    let mut export = BookmarkExport {
+        version: BOOKMARK_EXPORT_VERSION,
+        bookmarks: server_bookmarks.into_iter()
+            .map(|bookmark| ExportedBookmark {
+                name: bookmark.bookmark_name,
+                host: bookmark.host,
+                port: bookmark.port,
+                description: bookmark.description,
+                emoji: bookmark.emoji,
+            })
+            .collect(),
+        groups: Vec::new(),
+        settings: match settings {
+            Some(settings) => ExportedSettings {
+                infocmds_required_role: settings.infocmds_required_role,
+                alerts: Some(ExportedAlertSettings {
+                    availability_channel: settings.availability_channel,
+                    utc_offset: settings.utc_offset,
+                    quiet_start: settings.quiet_start,
+                    quiet_end: settings.quiet_end,
+                    digest_minutes: settings.digest_minutes,
+                }),
+            },
+            None => ExportedSettings::default()
+        },
+    };
+
+    for group in groups {
+        export.groups.push(ExportedGroup {
+            name: group.group_name,
+            bookmarks: group_bookmarks(&ctx.data().database, group.id).await
+                .into_iter()
+                .map(|bookmark| bookmark.bookmark_name)
+                .collect(),
+        });
+    }
+
+    let json = serde_json::to_vec_pretty(&export)?;
+    ctx.send(poise::CreateReply::default()
+        .content(format!("{}, exported {} bookmarks and {} groups!", ctx.author(), export.bookmarks.len(), export.groups.len()))
+        .attachment(serenity::CreateAttachment::bytes(json, "bookmarks.json"))
+    ).await?;
+
+    Ok(())
+}
+
+/// Import bookmarks, groups and settings from a /bkexport file
+#[poise::command(
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS",
+    guild_only
+)]
+pub async fn bkimport(
+    ctx: Context<'_>,
+    #[description = "JSON file created by /bkexport"] file: serenity::Attachment,
+    #[description = "How to handle bookmarks that already exist"] mode: ImportMode,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    let guild_id = ctx.guild_id().unwrap().get();
+
+    // Download and validate the file
+    if file.size > MAX_IMPORT_SIZE {
+        return Err("That file is too large to be a bookmark export!".into());
+    }
+
+    let import: BookmarkExport = match serde_json::from_slice(&file.download().await?) {
+        Ok(import) => import,
+        Err(e) => return Err(format!("That file isn't a valid bookmark export! ({e})").into())
+    };
+    validate_import(&import)?;
+
+    // Only keep the required role and alert channel if they exist in this guild
+    let required_role = import.settings.infocmds_required_role.filter(|role_id| {
+        ctx.guild().is_some_and(|guild| guild.roles.contains_key(&serenity::RoleId::new(*role_id)))
+    });
+    let availability_channel = import.settings.alerts.as_ref()
+        .and_then(|alerts| alerts.availability_channel)
+        .filter(|channel_id| ctx.guild().is_some_and(|guild| guild.channels.contains_key(&serenity::ChannelId::new(*channel_id))));
+
+    // Work out what would change
+    let server_bookmarks: Vec<ServerBookmark> = sqlx::query_as!(ServerBookmark, "SELECT * FROM server_bookmarks WHERE guild_id = ?", guild_id)
+        .fetch_all(&ctx.data().database)
+        .await
+        .unwrap();
+
+    let groups: Vec<BookmarkGroup> = sqlx::query_as!(BookmarkGroup, "SELECT * FROM bookmark_groups WHERE guild_id = ?", guild_id)
+        .fetch_all(&ctx.data().database)
+        .await
+        .unwrap();
+
+    let plan = plan_import(&import, mode, &server_bookmarks, &groups)?;
+
+    // Preview and wait for confirmation
+    let ctx_id = ctx.id();
+    let confirm_button_id = format!("{}confirm", ctx_id);
+    let cancel_button_id = format!("{}cancel", ctx_id);
+    let author_id = ctx.author().id;
+
+    let mut preview = plan.preview();
+    if let Some(role_id) = required_role {
+        preview = preview.field("Required role:", format!("<@&{role_id}>"), false);
+    }
+    if let Some(alerts) = &import.settings.alerts {
+        preview = preview.field("Alert settings:", describe_alert_settings(alerts, availability_channel), false);
+    }
+
+    let buttons = |disabled: bool| vec![serenity::CreateActionRow::Buttons(vec![
+        serenity::CreateButton::new(&confirm_button_id).label("Import").style(serenity::ButtonStyle::Success).disabled(disabled),
+        serenity::CreateButton::new(&cancel_button_id).label("Cancel").style(serenity::ButtonStyle::Danger).disabled(disabled),
+    ])];
+
+    let reply = ctx.send(poise::CreateReply::default()
+        .embed(preview.clone())
+        .components(buttons(false))
+    ).await?;
+
+    let press = serenity::collector::ComponentInteractionCollector::new(ctx)
+        .filter(move |press| press.data.custom_id.starts_with(&ctx_id.to_string()) && press.user.id == author_id)
+        .timeout(std::time::Duration::from_secs(120))
+        .await;
+
+    let press = match press {
+        Some(press) if press.data.custom_id == confirm_button_id => press,
+        _ => {
+            reply.edit(ctx, poise::CreateReply::default()
+                .content("Import cancelled, nothing was changed.")
+                .embed(preview)
+                .components(buttons(true))
+            ).await?;
+            return Ok(());
+        }
+    };
+
+    press.defer(ctx.serenity_context()).await?;
+
+    // Apply everything in one transaction
+    let mut tx = ctx.data().database.begin().await?;
+
+    for bookmark in &import.bookmarks {
+        if plan.added.contains(&bookmark.name) {
+            sqlx::query!("INSERT INTO server_bookmarks (guild_id, bookmark_name, host, port, description, emoji) VALUES (?, ?, ?, ?, ?, ?)",
+                guild_id, bookmark.name, bookmark.host, bookmark.port, bookmark.description, bookmark.emoji)
+                .execute(&mut *tx)
+                .await?;
+        } else if plan.replaced.contains(&bookmark.name) {
+            sqlx::query!("UPDATE server_bookmarks SET host = ?, port = ?, description = ?, emoji = ? WHERE guild_id = ? AND bookmark_name = ?",
+                bookmark.host, bookmark.port, bookmark.description, bookmark.emoji, guild_id, bookmark.name)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+
+    for group in &import.groups {
+        let existing = groups.iter().find(|existing| existing.group_name == group.name);
+        let group_id = match existing {
+            Some(existing) => {
+                if mode == ImportMode::Replace {
+                    sqlx::query!("DELETE FROM bookmark_group_members WHERE group_id = ?", existing.id)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+
+                existing.id as u64
+            },
+            None => sqlx::query!("INSERT INTO bookmark_groups (guild_id, group_name) VALUES (?, ?)", guild_id, group.name)
+                .execute(&mut *tx)
+                .await?
+                .last_insert_id()
+        };
+
+        for bookmark in &group.bookmarks {
+            sqlx::query!("INSERT IGNORE INTO bookmark_group_members (group_id, bookmark_id) SELECT ?, id FROM server_bookmarks WHERE guild_id = ? AND bookmark_name = ?",
+                group_id, guild_id, bookmark)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+
+    if let Some(role_id) = required_role {
+        sqlx::query!("UPDATE guild_settings SET infocmds_required_role = ? WHERE guild_id = ?", role_id, guild_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    if let Some(alerts) = &import.settings.alerts {
+        sqlx::query!("UPDATE guild_settings SET availability_channel = ?, utc_offset = ?, quiet_start = ?, quiet_end = ?, digest_minutes = ? WHERE guild_id = ?",
+            availability_channel, alerts.utc_offset, alerts.quiet_start, alerts.quiet_end, alerts.digest_minutes, guild_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+
+    reply.edit(ctx, poise::CreateReply::default()
+        .content(format!("{}, imported the bookmarks!", ctx.author()))
+        .embed(plan.preview())
+        .components(buttons(true))
+    ).await?;
+
+    Ok(())
+}
+
+// Changes an import would make, by name
+struct ImportPlan {
+    added: Vec<String>,
+    replaced: Vec<String>,
+    skipped: Vec<String>,
+    groups_added: Vec<String>,
+    groups_updated: Vec<String>,
+}
+
+impl ImportPlan {
+    fn preview(&self) -> serenity::CreateEmbed {
+        let list = |names: &Vec<String>| if names.is_empty() {
+            String::from("None")
+        } else {
+            names.join(", ").chars().take(1000).collect()
+        };
+
+        serenity::CreateEmbed::new()
+            .colour(0xFF0000)
+            .title("Bookmark Import")
+            .field(format!("New bookmarks ({}):", self.added.len()), list(&self.added), false)
+            .field(format!("Replaced bookmarks ({}):", self.replaced.len()), list(&self.replaced), false)
+            .field(format!("Skipped bookmarks ({}):", self.skipped.len()), list(&self.skipped), false)
+            .field(format!("New groups ({}):", self.groups_added.len()), list(&self.groups_added), false)
+            .field(format!("Updated groups ({}):", self.groups_updated.len()), list(&self.groups_updated), false)
+    }
+}
+
+// Alert settings an import would set, for the preview
+fn describe_alert_settings(alerts: &ExportedAlertSettings, availability_channel: Option<u64>) -> String {
+    format!("**Availability channel:** {}\n**Timezone:** {}\n**Quiet hours:** {}\n**Digest:** {}",
+        availability_channel.map(|id| format!("<#{id}>")).unwrap_or(String::from("None")),
+        format_utc_offset(alerts.utc_offset),
+        match (alerts.quiet_start, alerts.quiet_end) {
+            (Some(start), Some(end)) => format!("{start:02}:00 to {end:02}:00"),
+            _ => String::from("Off")
+        },
+        match alerts.digest_minutes {
+            0 => String::from("Off"),
+            minutes => format!("{minutes} minutes")
+        }
+    )
+}
+
+// Check an import file makes sense on its own
+fn validate_import(import: &BookmarkExport) -> Result<(), Error> {
+    if import.version == 0 || import.version > BOOKMARK_EXPORT_VERSION {
+        return Err(format!("Unsupported bookmark export version {}!", import.version).into());
+    }
+
+    // Same limits as /settimezone, /setquiethours and /setdigest
+    if let Some(alerts) = &import.settings.alerts {
+        if !(-12 * 60..=14 * 60).contains(&alerts.utc_offset) {
+            return Err("The export has an invalid timezone!".into());
+        }
+        let valid_quiet_hours = match (alerts.quiet_start, alerts.quiet_end) {
+            (Some(start), Some(end)) => start < 24 && end < 24 && start != end,
+            (None, None) => true,
+            _ => false
+        };
+        if !valid_quiet_hours {
+            return Err("The export has invalid quiet hours!".into());
+        }
+        if alerts.digest_minutes > 1440 {
+            return Err("The export has an invalid digest length!".into());
+        }
+    }
+
+    let mut names: HashSet<&str> = HashSet::new();
+    let mut servers: HashSet<(String, u32)> = HashSet::new();
+    for bookmark in &import.bookmarks {
+        if bookmark.name.trim().is_empty() || bookmark.host.trim().is_empty() {
+            return Err("Every bookmark needs a name and a host!".into());
+        }
+        if bookmark.port == 0 || bookmark.port > 65535 {
+            return Err(format!("Bookmark {} has an invalid port!", bookmark.name).into());
+        }
+        if !names.insert(&bookmark.name) {
+            return Err(format!("The name {} is used more than once!", bookmark.name).into());
+        }
+        // Servers can only be bookmarked once per guild
+        if !servers.insert((bookmark.host.trim().to_lowercase(), bookmark.port)) {
+            return Err(format!("The server {}:{} is bookmarked more than once!", bookmark.host, bookmark.port).into());
+        }
+    }
+
+    for group in &import.groups {
+        if group.name.trim().is_empty() {
+            return Err("Every group needs a name!".into());
+        }
+        if !names.insert(&group.name) {
+            return Err(format!("The name {} is used more than once!", group.name).into());
+        }
+    }
+
+    Ok(())
+}
+
+// Compare an import against the guild's current bookmarks and groups
+fn plan_import(import: &BookmarkExport, mode: ImportMode, server_bookmarks: &[ServerBookmark], groups: &[BookmarkGroup]) -> Result<ImportPlan, Error> {
+    let existing: HashMap<&str, &ServerBookmark> = server_bookmarks.iter().map(|bookmark| (bookmark.bookmark_name.as_str(), bookmark)).collect();
+    let group_names: HashSet<&str> = groups.iter().map(|group| group.group_name.as_str()).collect();
+
+    let mut plan = ImportPlan {
+        added: Vec::new(),
+        replaced: Vec::new(),
+        skipped: Vec::new(),
+        groups_added: Vec::new(),
+        groups_updated: Vec::new(),
+    };
+
+    for bookmark in &import.bookmarks {
+        if group_names.contains(bookmark.name.as_str()) {
+            return Err(format!("The bookmark {} has the same name as an existing group!", bookmark.name).into());
+        }
+
+        // Servers can only be bookmarked once per guild
+        let same_server = server_bookmarks.iter().find(|other| {
+            other.host == bookmark.host && other.port == bookmark.port && other.bookmark_name != bookmark.name
+        });
+
+        if let Some(other) = same_server {
+            plan.skipped.push(format!("{} (already bookmarked as {})", bookmark.name, other.bookmark_name));
+            continue;
+        }
+
+        match (existing.get(bookmark.name.as_str()), mode) {
+            (None, _) => plan.added.push(bookmark.name.clone()),
+            (Some(_), ImportMode::Replace) => plan.replaced.push(bookmark.name.clone()),
+            (Some(_), ImportMode::Merge) => plan.skipped.push(format!("{} (already exists)", bookmark.name)),
+        }
+    }
+
+    for group in &import.groups {
+        if existing.contains_key(group.name.as_str()) {
+            return Err(format!("The group {} has the same name as an existing bookmark!", group.name).into());
+        }
+
+        if group_names.contains(group.name.as_str()) {
+            plan.groups_updated.push(group.name.clone());
+        } else {
+            plan.groups_added.push(group.name.clone());
+        }
+    }
+
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn import(bookmarks: &[(&str, &str, u32)]) -> BookmarkExport {
+        BookmarkExport {
+            version: BOOKMARK_EXPORT_VERSION,
+            bookmarks: bookmarks.iter()
+                .map(|(name, host, port)| ExportedBookmark {
+                    name: name.to_string(),
+                    host: host.to_string(),
+                    port: *port,
+                    description: None,
+                    emoji: None,
+                })
+                .collect(),
+            groups: Vec::new(),
+            settings: ExportedSettings::default(),
+        }
+    }
+
+    #[test]
+    fn distinct_bookmarks_are_valid() {
+        assert!(validate_import(&import(&[("a", "1.2.3.4", 28785), ("b", "1.2.3.4", 10000)])).is_ok());
+    }
+
+    #[test]
+    fn duplicate_names_are_rejected() {
+        assert!(validate_import(&import(&[("a", "1.2.3.4", 28785), ("a", "5.6.7.8", 28785)])).is_err());
+    }
+
+    #[test]
+    fn alert_settings_are_checked() {
+        let mut export = import(&[]);
+        export.settings.alerts = Some(ExportedAlertSettings { utc_offset: -330, quiet_start: Some(22), quiet_end: Some(7), ..Default::default() });
+        assert!(validate_import(&export).is_ok());
+
+        export.settings.alerts = Some(ExportedAlertSettings { utc_offset: 900, ..Default::default() });
+        assert!(validate_import(&export).is_err());
+
+        export.settings.alerts = Some(ExportedAlertSettings { quiet_start: Some(22), ..Default::default() });
+        assert!(validate_import(&export).is_err());
+
+        export.settings.alerts = Some(ExportedAlertSettings { digest_minutes: 2000, ..Default::default() });
+        assert!(validate_import(&export).is_err());
+    }
+
+    #[test]
+    fn older_versions_still_import() {
+        let mut export = import(&[]);
+        export.version = 1;
+        assert!(validate_import(&export).is_ok());
+
+        export.version = BOOKMARK_EXPORT_VERSION + 1;
+        assert!(validate_import(&export).is_err());
+    }
+
+    #[test]
+    fn duplicate_servers_are_rejected() {
+        assert!(validate_import(&import(&[("a", "1.2.3.4", 28785), ("b", "1.2.3.4", 28785)])).is_err());
+        assert!(validate_import(&import(&[("a", "Sauer.example.com", 28785), ("b", "sauer.example.com", 28785)])).is_err());
+    }
+}
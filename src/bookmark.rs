@@ -1,53 +1,110 @@
-use crate::{Context, Error};
-use crate::data::ServerBookmark;
+use crate::{Context, Data, Error};
+use crate::data::{fuzzy_rank, option_value, paginate, parse_address, DetailedServer, ServerBookmark};
 use crate::admin::info_role;
-use crate::server::{get_server_info, build_server_embed};
+use crate::group::{autocomplete_group, find_group, group_bookmarks};
+use crate::personal::{autocomplete_user_bookmark, find_user_bookmark};
+use crate::poller::{next_snapshot, SnapshotBus};
+use crate::server::{autocomplete_host, autocomplete_roster, fresh_snapshot, get_cached_server_list, get_live_server_info, get_server_info, send_server_embed, verify_server};
+use poise::serenity_prelude as serenity;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+// Bookmarked servers queried at once by /bkstatus
+const STATUS_PARALLELISM: usize = 4;
+
+// Time between updates of when bookmarks were last seen online
+const HEALTH_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(300);
 
 /// Grab server information from a bookmark
 #[poise::command(
     slash_command,
     check = "info_role",
-    user_cooldown = 10,
-    guild_only
+    user_cooldown = 10
 )]
 pub async fn bk(
     ctx: Context<'_>,
-    #[description = "Name of the server bookmark"] bookmark: String,
+    #[description = "Name of the server bookmark or bookmark group"]
+    #[autocomplete = "autocomplete_bookmark_or_group"] bookmark: String,
     #[description = "Player in game"]
+    #[autocomplete = "autocomplete_bookmark_player"]
     #[max_length = 15] username: Option<String>,
 ) -> Result<(), Error> {
     ctx.defer().await?;
-    let guild_id = ctx.guild_id().unwrap().get();
+    let guild_id = ctx.guild_id().map(|id| id.get());
 
     // Verify then grab information in DB
-    let count = sqlx::query!("SELECT COUNT(bookmark_name) AS count FROM server_bookmarks WHERE guild_id = ? AND bookmark_name = ?", guild_id, bookmark)
-        .fetch_one(&ctx.data().database)
-        .await
-        .unwrap();
+    let (host, port) = match find_bookmark_address(&ctx.data().database, guild_id, ctx.author().id.get(), &bookmark).await {
+        Some(address) => address,
+        None => {
+            // Show an aggregated view when the name is a group
+            let group_info = match guild_id {
+                Some(guild_id) => find_group(&ctx.data().database, guild_id, &bookmark).await,
+                None => None
+            };
+            let group_info = match group_info {
+                Some(info) => info,
+                None => return Err("No bookmark or group by that name exists!".into())
+            };
 
-    if count.count == 0 {
-        return Err("No bookmark by that name exists!".into());
-    }
+            if username.is_some() {
+                return Err("Players can only be looked up on a single bookmark!".into());
+            }
 
-    let bookmark_info = sqlx::query_as!(ServerBookmark, "SELECT * FROM server_bookmarks WHERE guild_id = ? AND bookmark_name = ?", guild_id, bookmark)
-        .fetch_one(&ctx.data().database)
-        .await
-        .unwrap();
+            return send_group_status(ctx, &group_info.group_name, group_bookmarks(&ctx.data().database, group_info.id).await).await;
+        }
+    };
 
-    let page_url = format!("https://sauertracker.net/server/{}/{}", bookmark_info.host, bookmark_info.port);
+    let page_url = format!("https://sauertracker.net/server/{}/{}", host, port);
 
-    let server_data = match get_server_info(&ctx.data().client, bookmark_info.host.clone(), bookmark_info.port.clone()).await {
+    let server_data = match get_live_server_info(ctx.data(), host, port).await {
         Ok(data) => data,
         Err(e) => return Err(e)
     };
 
-    let server_embed = match build_server_embed(server_data, username, page_url) {
-        Ok(embed) => embed,
-        Err(e) => return Err(e)
-    };
-    ctx.send(poise::CreateReply::default().embed(server_embed)).await?;
+    send_server_embed(ctx, server_data, username, page_url).await
+}
 
-    Ok(())
+/// Show the status of every bookmarked server
+#[poise::command(
+    slash_command,
+    check = "info_role",
+    user_cooldown = 10,
+    guild_only
+)]
+pub async fn bkstatus(
+    ctx: Context<'_>,
+    #[description = "Only show bookmarks in this group"]
+    #[autocomplete = "autocomplete_group"] group: Option<String>,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    let guild_id = ctx.guild_id().unwrap().get();
+
+    match group {
+        Some(group) => {
+            let group_info = match find_group(&ctx.data().database, guild_id, &group).await {
+                Some(info) => info,
+                None => return Err("No group with that name exists!".into())
+            };
+
+            send_group_status(ctx, &group_info.group_name, group_bookmarks(&ctx.data().database, group_info.id).await).await
+        },
+        None => {
+            let server_bookmarks: Vec<ServerBookmark> = sqlx::query_as!(ServerBookmark, "SELECT * FROM server_bookmarks WHERE guild_id = ?", guild_id)
+                .fetch_all(&ctx.data().database)
+                .await
+                .unwrap();
+
+            if server_bookmarks.is_empty() {
+                return Err("No bookmarks saved!".into());
+            }
+
+            let statuses = fetch_bookmark_statuses(ctx.data(), &server_bookmarks).await;
+            paginate(ctx, status_pages(String::from("Bookmarked Servers"), &server_bookmarks, &statuses), false).await?;
+
+            Ok(())
+        }
+    }
 }
 
 /// Create a server bookmark
@@ -59,23 +116,22 @@ pub async fn bk(
 pub async fn bkadd(
     ctx: Context<'_>,
     #[description = "Name for the bookmark"] bookmark: String,
-    #[description = "Server address/ip"] host: String,
-    #[description = "Server port (Default: 28785)"] port: Option<u32>
+    #[description = "Server address/ip, host:port or /connect line"]
+    #[autocomplete = "autocomplete_host"] host: String,
+    #[description = "Server port (Default: 28785)"] port: Option<u32>,
+    #[description = "Short description shown in the bookmark list"]
+    #[max_length = 200] description: Option<String>,
+    #[description = "Emoji shown next to the bookmark"]
+    #[max_length = 64] emoji: Option<String>,
+    #[description = "Add the bookmark even if the server isn't currently listed"] force: Option<bool>
 ) -> Result<(), Error> {
+    ctx.defer().await?;
     let guild_id = ctx.guild_id().unwrap().get();
 
-    let port = match port {
-        Some(port) => port,
-        None => 28785
-    };
+    let (host, port) = parse_address(&host, port)?;
 
     // Verify information in DB
-    let count = sqlx::query!("SELECT COUNT(bookmark_name) AS count FROM server_bookmarks WHERE guild_id = ? AND bookmark_name = ?", guild_id, bookmark)
-        .fetch_one(&ctx.data().database)
-        .await
-        .unwrap();
-
-    if count.count > 0 {
+    if name_taken(&ctx.data().database, guild_id, &bookmark).await {
         return Err("That name is already used!".into());
     }
 
@@ -88,8 +144,19 @@ pub async fn bkadd(
         return Err("That server is already bookmarked!".into());
     }
 
+    // Check the server is live unless told otherwise
+    let last_seen = if force.unwrap_or(false) {
+        None
+    } else {
+        if let Err(e) = verify_server(&ctx.data().client, host.clone(), port).await {
+            return Err(format!("{e} Use `force:True` to bookmark it anyway.").into());
+        }
+
+        Some(chrono::Utc::now().naive_utc())
+    };
+
     // Add to DB
-    sqlx::query!("INSERT INTO server_bookmarks (guild_id, bookmark_name, host, port) VALUES (?, ?, ?, ?)", guild_id, bookmark, host, port)
+    sqlx::query!("INSERT INTO server_bookmarks (guild_id, bookmark_name, host, port, description, emoji, last_seen, last_checked) VALUES (?, ?, ?, ?, ?, ?, ?, ?)", guild_id, bookmark, host, port, description, emoji, last_seen, last_seen)
         .execute(&ctx.data().database)
         .await
         .unwrap();
@@ -100,6 +167,85 @@ pub async fn bkadd(
     Ok(())
 }
 
+/// Rename a server bookmark or change its address, description or emoji
+#[poise::command(
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS",
+    guild_only
+)]
+pub async fn bkedit(
+    ctx: Context<'_>,
+    #[description = "Name of the server bookmark"]
+    #[autocomplete = "autocomplete_bookmark"] bookmark: String,
+    #[description = "New name for the bookmark"] name: Option<String>,
+    #[description = "New server address/ip, host:port or /connect line"]
+    #[autocomplete = "autocomplete_host"] host: Option<String>,
+    #[description = "New server port"] port: Option<u32>,
+    #[description = "New description, or - to remove it"]
+    #[max_length = 200] description: Option<String>,
+    #[description = "New emoji, or - to remove it"]
+    #[max_length = 64] emoji: Option<String>
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+
+    if name.is_none() && host.is_none() && port.is_none() && description.is_none() && emoji.is_none() {
+        return Err("Nothing to change! Give a new name, address, description or emoji.".into());
+    }
+
+    // Verify then grab information in DB
+    let bookmark_info = match sqlx::query_as!(ServerBookmark, "SELECT * FROM server_bookmarks WHERE guild_id = ? AND bookmark_name = ?", guild_id, bookmark)
+        .fetch_optional(&ctx.data().database)
+        .await
+        .unwrap()
+    {
+        Some(info) => info,
+        None => return Err("No bookmark with that name exists!".into())
+    };
+
+    let new_name = name.unwrap_or(bookmark_info.bookmark_name.clone());
+    if new_name != bookmark_info.bookmark_name && name_taken(&ctx.data().database, guild_id, &new_name).await {
+        return Err("That name is already used!".into());
+    }
+
+    let (new_host, new_port) = match host {
+        Some(host) => parse_address(&host, port)?,
+        None => (bookmark_info.host.clone(), port.unwrap_or(bookmark_info.port))
+    };
+    if new_host != bookmark_info.host || new_port != bookmark_info.port {
+        let count = sqlx::query!("SELECT COUNT(host) AS count FROM server_bookmarks WHERE guild_id = ? AND host = ? AND port = ?", guild_id, new_host, new_port)
+            .fetch_one(&ctx.data().database)
+            .await
+            .unwrap();
+
+        if count.count > 0 {
+            return Err("That server is already bookmarked!".into());
+        }
+    }
+
+    // "-" clears a field, leaving it out keeps the current value
+    let new_description = match description.as_deref() {
+        Some("-") => None,
+        Some(_) => description,
+        None => bookmark_info.description
+    };
+    let new_emoji = match emoji.as_deref() {
+        Some("-") => None,
+        Some(_) => emoji,
+        None => bookmark_info.emoji
+    };
+
+    sqlx::query!("UPDATE server_bookmarks SET bookmark_name = ?, host = ?, port = ?, description = ?, emoji = ? WHERE id = ?",
+        new_name, new_host, new_port, new_description, new_emoji, bookmark_info.id)
+        .execute(&ctx.data().database)
+        .await
+        .unwrap();
+
+    let msg = format!("{}, updated the server bookmark {}! Address: {}:{}", ctx.author(), new_name, new_host, new_port);
+    ctx.say(msg).await?;
+
+    Ok(())
+}
+
 /// Remove a server bookmark
 #[poise::command(
     slash_command,
@@ -108,7 +254,8 @@ pub async fn bkadd(
 )]
 pub async fn bkdelete(
     ctx: Context<'_>,
-    #[description = "Name of the server bookmark"] bookmark: String
+    #[description = "Name of the server bookmark"]
+    #[autocomplete = "autocomplete_bookmark"] bookmark: String
 ) -> Result<(), Error> {
     let guild_id = ctx.guild_id().unwrap().get();
 
@@ -134,6 +281,9 @@ pub async fn bkdelete(
     Ok(())
 }
 
+// Bookmarks not listed by the tracker for this long are flagged as stale
+const STALE_AFTER_DAYS: i64 = 7;
+
 /// List all bookmarks for the guild
 #[poise::command(
     slash_command,
@@ -153,17 +303,266 @@ pub async fn bklist(ctx: Context<'_>) -> Result<(), Error> {
         return Err("No bookmarks saved!".into());
     }
 
-    // Build, send message
-    let mut bk_list = String::new();
-    for (_, bookmark) in server_bookmarks.iter().enumerate() {
-        bk_list = format!("{bk_list}\n- {0} - `/server host:{1} port:{2}`",
-            bookmark.bookmark_name,
-            bookmark.host,
-            bookmark.port);
+    // Build, send pages
+    let stale_before = chrono::Utc::now().naive_utc() - chrono::Duration::days(STALE_AFTER_DAYS);
+    let mut pages: Vec<serenity::CreateEmbed> = Vec::new();
+    for chunk in server_bookmarks.chunks(10) {
+        let mut bk_list = String::new();
+        for bookmark in chunk {
+            bk_list = format!("{bk_list}- {0}**{1}** - `/server host:{2} port:{3}`\n",
+                bookmark.emoji.as_ref().map(|emoji| format!("{emoji} ")).unwrap_or_default(),
+                bookmark.bookmark_name,
+                bookmark.host,
+                bookmark.port);
+
+            if let Some(description) = &bookmark.description {
+                bk_list = format!("{bk_list} - *{description}*\n");
+            }
+
+            match bookmark.last_seen {
+                None if bookmark.last_checked.is_none() => bk_list = format!("{bk_list} - Not checked yet\n"),
+                None => bk_list = format!("{bk_list} - ⚠️ Never seen online\n"),
+                Some(last_seen) if last_seen < stale_before => {
+                    bk_list = format!("{bk_list} - ⚠️ Last seen online <t:{}:R>\n", last_seen.and_utc().timestamp());
+                },
+                _ => {}
+            }
+        }
+
+        pages.push(serenity::CreateEmbed::new()
+            .title("Server Bookmarks")
+            .description(bk_list));
+    }
+
+    if server_bookmarks.iter().any(|bookmark| bookmark.last_checked.is_some() && bookmark.last_seen.map_or(true, |last_seen| last_seen < stale_before)) {
+        pages = pages.into_iter()
+            .map(|page| page.field("Stale bookmarks:", format!("Bookmarks not seen online in {STALE_AFTER_DAYS} days can be removed with `/bkdelete`."), false))
+            .collect();
     }
 
-    let msg = format!("__**Server Bookmarks:**__{bk_list}");
-    ctx.say(msg).await?;
+    paginate(ctx, pages, false).await?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+// Resolve a bookmark name to an address, preferring personal bookmarks over the guild's
+pub async fn find_bookmark_address(database: &sqlx::MySqlPool, guild_id: Option<u64>, user_id: u64, bookmark: &str) -> Option<(String, u32)> {
+    if let Some(bookmark_info) = find_user_bookmark(database, user_id, bookmark).await {
+        return Some((bookmark_info.host, bookmark_info.port));
+    }
+
+    let guild_id = guild_id?;
+    sqlx::query_as!(ServerBookmark, "SELECT * FROM server_bookmarks WHERE guild_id = ? AND bookmark_name = ?", guild_id, bookmark)
+        .fetch_optional(database)
+        .await
+        .unwrap()
+        .map(|bookmark_info| (bookmark_info.host, bookmark_info.port))
+}
+
+// Bookmarks and groups share one set of names per guild
+pub async fn name_taken(database: &sqlx::MySqlPool, guild_id: u64, name: &str) -> bool {
+    let bookmarks = sqlx::query!("SELECT COUNT(bookmark_name) AS count FROM server_bookmarks WHERE guild_id = ? AND bookmark_name = ?", guild_id, name)
+        .fetch_one(database)
+        .await
+        .unwrap();
+    let groups = sqlx::query!("SELECT COUNT(group_name) AS count FROM bookmark_groups WHERE guild_id = ? AND group_name = ?", guild_id, name)
+        .fetch_one(database)
+        .await
+        .unwrap();
+
+    bookmarks.count > 0 || groups.count > 0
+}
+
+// Fetch and show the status of a group's bookmarks
+async fn send_group_status(ctx: Context<'_>, group_name: &str, server_bookmarks: Vec<ServerBookmark>) -> Result<(), Error> {
+    if server_bookmarks.is_empty() {
+        return Err("That group has no bookmarks!".into());
+    }
+
+    let statuses = fetch_bookmark_statuses(ctx.data(), &server_bookmarks).await;
+    paginate(ctx, status_pages(format!("Group: {group_name}"), &server_bookmarks, &statuses), false).await?;
+
+    Ok(())
+}
+
+// Status pages with total players and the busiest server above the list
+pub fn status_pages(title: String, server_bookmarks: &[ServerBookmark], statuses: &[Result<DetailedServer, Error>]) -> Vec<serenity::CreateEmbed> {
+    let total_players: i64 = statuses.iter().flatten().map(|server| server.clients).sum();
+    let busiest = server_bookmarks.iter()
+        .zip(statuses.iter())
+        .filter_map(|(bookmark, status)| status.as_ref().ok().map(|server| (bookmark, server)))
+        .filter(|(_, server)| server.clients > 0)
+        .max_by_key(|(_, server)| server.clients);
+
+    let header = format!("**Total players:** {}\n**Busiest:** {}\n\n",
+        total_players,
+        match busiest {
+            Some((bookmark, server)) => format!("{} ({}/{} | {} {})", bookmark.bookmark_name, server.clients, server.maxClients, server.gameMode, server.mapName),
+            None => String::from("None")
+        }
+    );
+
+    let mut pages: Vec<serenity::CreateEmbed> = Vec::new();
+    for chunk in server_bookmarks.iter().zip(statuses.iter()).collect::<Vec<_>>().chunks(15) {
+        let mut status_list = header.clone();
+        for (bookmark, status) in chunk {
+            status_list = format!("{status_list}{}\n", bookmark_status_line(bookmark, status));
+        }
+
+        pages.push(serenity::CreateEmbed::new()
+            .colour(0xFF0000)
+            .title(title.clone())
+            .description(status_list));
+    }
+
+    pages
+}
+
+// Suggest personal bookmarks first, then the guild's bookmarks and groups
+async fn autocomplete_bookmark_or_group(ctx: Context<'_>, partial: &str) -> Vec<serenity::AutocompleteChoice> {
+    let mut choices = autocomplete_user_bookmark(ctx, partial).await;
+    choices.extend(autocomplete_bookmark(ctx, partial).await);
+    for group in autocomplete_group(ctx, partial).await {
+        choices.push(serenity::AutocompleteChoice::new(format!("{group} (group)"), group));
+    }
+
+    choices.truncate(25);
+    choices
+}
+
+// Suggest the guild's bookmarks, labelled with their address and live player count
+pub async fn autocomplete_bookmark(ctx: Context<'_>, partial: &str) -> Vec<serenity::AutocompleteChoice> {
+    let guild_id = match ctx.guild_id() {
+        Some(id) => id.get(),
+        None => return Vec::new()
+    };
+
+    let server_bookmarks: Vec<ServerBookmark> = sqlx::query_as!(ServerBookmark, "SELECT * FROM server_bookmarks WHERE guild_id = ?", guild_id)
+        .fetch_all(&ctx.data().database)
+        .await
+        .unwrap_or_default();
+    let servers = get_cached_server_list(ctx.data()).await.unwrap_or_default();
+    let snapshot = ctx.data().snapshots.latest();
+
+    let mut choices = Vec::new();
+    for bookmark in fuzzy_rank(partial, server_bookmarks, |bookmark| &bookmark.bookmark_name) {
+        // The tracker lists servers by IP, which the poller resolves for every bookmark
+        let host = match &snapshot {
+            Some(snapshot) => snapshot.address(&bookmark.host),
+            None => bookmark.host.clone()
+        };
+        let players = match servers.iter().find(|server| server.host == host && server.port == bookmark.port as i64) {
+            Some(server) => format!("{}/{} players", server.clients, server.maxClients),
+            None => String::from("offline")
+        };
+
+        let label: String = format!("{} - {}:{} ({})", bookmark.bookmark_name, bookmark.host, bookmark.port, players)
+            .chars()
+            .take(100)
+            .collect();
+        choices.push(serenity::AutocompleteChoice::new(label, bookmark.bookmark_name));
+    }
+
+    choices
+}
+
+// Suggest players on the server picked in the bookmark option
+async fn autocomplete_bookmark_player(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let address = match option_value(ctx, "bookmark") {
+        Some(bookmark) => find_bookmark_address(&ctx.data().database, ctx.guild_id().map(|id| id.get()), ctx.author().id.get(), &bookmark).await,
+        None => None
+    };
+
+    match address {
+        Some((host, port)) => autocomplete_roster(ctx, host, port, partial).await,
+        None => Vec::new()
+    }
+}
+
+// Record when bookmarked servers are listed by the tracker, every HEALTH_CHECK_INTERVAL
+pub async fn health_check(database: sqlx::MySqlPool, snapshots: Arc<SnapshotBus>) {
+    let mut receiver = snapshots.subscribe();
+    let mut last_check: Option<std::time::Instant> = None;
+
+    while let Some(snapshot) = next_snapshot(&mut receiver).await {
+        if last_check.is_some_and(|checked_at| checked_at.elapsed() < HEALTH_CHECK_INTERVAL) {
+            continue;
+        }
+        last_check = Some(std::time::Instant::now());
+
+        let server_bookmarks: Vec<ServerBookmark> = sqlx::query_as!(ServerBookmark, "SELECT * FROM server_bookmarks")
+            .fetch_all(&database)
+            .await
+            .unwrap_or_default();
+
+        for bookmark in server_bookmarks {
+            let listed = snapshot.server(&bookmark.host, bookmark.port).is_some();
+
+            let updated = sqlx::query!("UPDATE server_bookmarks SET last_checked = NOW(), last_seen = IF(?, NOW(), last_seen) WHERE id = ?", listed, bookmark.id)
+                .execute(&database)
+                .await;
+            if let Err(e) = updated {
+                println!("[ ERROR ] Updating health of bookmark {} failed: {e}", bookmark.id);
+            }
+        }
+    }
+}
+
+// Every bookmark's server info in bookmark order. Servers the poller has fresh details for
+// are read from its snapshot, the rest are fetched concurrently, a few at a time.
+pub async fn fetch_bookmark_statuses(data: &Data, server_bookmarks: &[ServerBookmark]) -> Vec<Result<DetailedServer, Error>> {
+    let snapshot = fresh_snapshot(data);
+    let permits = Arc::new(Semaphore::new(STATUS_PARALLELISM));
+    let mut requests = JoinSet::new();
+
+    let mut statuses: Vec<Result<DetailedServer, Error>> = server_bookmarks.iter()
+        .map(|_| Err("The request for this server failed!".into()))
+        .collect();
+    for (i, bookmark) in server_bookmarks.iter().enumerate() {
+        if let Some(server) = snapshot.as_ref().and_then(|snapshot| snapshot.detailed_server(&bookmark.host, bookmark.port)) {
+            statuses[i] = Ok(server.clone());
+            continue;
+        }
+
+        let client = data.client.clone();
+        let permits = permits.clone();
+        let (host, port) = (bookmark.host.clone(), bookmark.port);
+
+        requests.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            (i, get_server_info(&client, host, port).await)
+        });
+    }
+
+    while let Some(res) = requests.join_next().await {
+        if let Ok((i, status)) = res {
+            statuses[i] = status;
+        }
+    }
+
+    statuses
+}
+
+// One line summary of a bookmarked server
+pub fn bookmark_status_line(bookmark: &ServerBookmark, status: &Result<DetailedServer, Error>) -> String {
+    let name = format!("{}**{}**",
+        bookmark.emoji.as_ref().map(|emoji| format!("{emoji} ")).unwrap_or_default(),
+        bookmark.bookmark_name
+    );
+
+    match status {
+        Ok(server) => format!("- {} - {}/{} | {} {}{}",
+            name,
+            server.clients,
+            server.maxClients,
+            server.gameMode,
+            server.mapName,
+            if server.gameMode != "coop_edit" {
+                format!(" - {}", server.timeLeftString)
+            } else {
+                String::new()
+            }
+        ),
+        Err(e) => format!("- ❌ {} - Unreachable: *{}*", name, e)
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{Context, Error};
-use crate::data::grab_api_data;
+use crate::data::{cached_autocomplete, fuzzy_rank, grab_api_data};
 use crate::admin::info_role;
 use poise::serenity_prelude as serenity;
 
@@ -11,7 +11,8 @@ use poise::serenity_prelude as serenity;
 )]
 pub async fn claninfo(
     ctx: Context<'_>,
-    #[description = "Clantag to search."] clantag: String,
+    #[description = "Clantag to search."]
+    #[autocomplete = "autocomplete_clantag"] clantag: String,
 ) -> Result<(), Error> {
     ctx.defer().await?;
 
@@ -98,4 +99,56 @@ pub async fn claninfo(
     ctx.send(poise::CreateReply::default().embed(clan_embed)).await?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+// Suggest clantags from the tracker's clan list
+pub async fn autocomplete_clantag(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let client = &ctx.data().client;
+    let clantags = cached_autocomplete(ctx.data(), String::from("clans"), fetch_clantags(client)).await;
+
+    fuzzy_rank(partial, clantags, |tag| tag.as_str())
+}
+
+// Every clantag known to the tracker
+pub async fn fetch_clantags(client: &reqwest::Client) -> Result<Vec<String>, Error> {
+    let api_link = String::from("https://sauertracker.net/api/clans");
+    let data = grab_api_data(client, api_link, &String::from("https://sauertracker.net/clans")).await?;
+
+    Ok(data.as_array()
+        .map(|clans| clans.iter().filter_map(|clan| clan["tag"].as_str().map(String::from)).collect())
+        .unwrap_or_default())
+}
+
+// Names of a clan's recently seen members
+pub async fn fetch_clan_members(client: &reqwest::Client, clantag: &str) -> Result<Vec<String>, Error> {
+    let api_link = clan_url("https://sauertracker.net/api/clan", clantag);
+    let page_url = clan_url("https://sauertracker.net/clan", clantag);
+    let data = grab_api_data(client, api_link, &page_url).await?;
+
+    if data["error"].as_str().is_some() {
+        return Err("No clan found with that clantag!".into());
+    }
+
+    Ok(data["members"].as_array()
+        .map(|members| members.iter().filter_map(|member| member["name"].as_str().map(String::from)).collect())
+        .unwrap_or_default())
+}
+
+// Tracker link for a clan. Tags can hold any character, so the tag is percent-encoded.
+fn clan_url(base: &str, clantag: &str) -> String {
+    let mut url = reqwest::Url::parse(base).unwrap();
+    url.path_segments_mut().unwrap().push(clantag);
+    url.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clantags_are_encoded_in_links() {
+        assert_eq!(clan_url("https://sauertracker.net/api/clan", "oO"), "https://sauertracker.net/api/clan/oO");
+        assert_eq!(clan_url("https://sauertracker.net/clan", "[a/b]"), "https://sauertracker.net/clan/[a%2Fb]");
+        assert_eq!(clan_url("https://sauertracker.net/clan", "a?b#c d"), "https://sauertracker.net/clan/a%3Fb%23c%20d");
+    }
+}
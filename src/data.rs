@@ -5,10 +5,15 @@ use poise::serenity_prelude as serenity;
 use poise::Context;
 use serde_json::Value;
 use serde::{Serialize, Deserialize};
-use crate::Error;
+use crate::{Data, Error};
+
+// How long autocomplete results are reused before asking the tracker again
+const AUTOCOMPLETE_TTL: std::time::Duration = std::time::Duration::from_secs(60);
+
+// Time an autocomplete lookup gets, well within Discord's 3 second deadline
+pub const AUTOCOMPLETE_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(1500);
 
 // Data structures
-#[allow(dead_code)]
 pub const MODENAMES: [&str; 23] = [
     "ffa",
     "coop_edit",
@@ -77,7 +82,7 @@ pub struct BasicServer {  // Used for the server list
     pub players: Vec<String>
 }
 
-#[derive(Default, Serialize, Deserialize, Debug)]
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
 pub struct DetailedServer { // Used for more detailed information
     pub descriptionStyled: String,
     pub description: String,
@@ -105,7 +110,7 @@ pub struct DetailedServer { // Used for more detailed information
     pub spectators: Option<Vec<String>>
 }
 
-#[derive(Default, Serialize, Deserialize, Debug)]
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
 pub struct ServerPlayer {
     pub name: String,
     pub frags: i64,
@@ -120,7 +125,7 @@ pub struct ServerPlayer {
     pub ping: i64,
 }
 
-#[derive(Default, Serialize, Deserialize, Debug)]
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
 pub struct Team {
     pub name: String,
     pub score: i64,
@@ -129,7 +134,7 @@ pub struct Team {
     pub players: Option<Vec<String>>
 }
 
-#[derive(Default, Serialize, Deserialize, Debug)]
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
 pub struct Info {
     pub website: String,
     pub demourl: String,
@@ -137,12 +142,96 @@ pub struct Info {
 }
 
 // DB specific structs
+#[derive(Clone)]
 pub struct ServerBookmark {
     pub id: i32,
     pub guild_id: u64,
     pub bookmark_name: String,
     pub host: String,
     pub port: u32,
+    pub description: Option<String>,
+    pub emoji: Option<String>,
+    pub last_seen: Option<chrono::NaiveDateTime>,
+    pub last_checked: Option<chrono::NaiveDateTime>,
+}
+
+pub struct UserBookmark {
+    pub id: i32,
+    pub user_id: u64,
+    pub bookmark_name: String,
+    pub host: String,
+    pub port: u32,
+}
+
+pub struct BookmarkGroup {
+    pub id: i32,
+    pub guild_id: u64,
+    pub group_name: String,
+}
+
+pub struct MapSubscription {
+    pub id: i32,
+    pub user_id: u64,
+    pub guild_id: Option<u64>,
+    pub channel_id: Option<u64>,
+    pub map_name: Option<String>,
+    pub game_mode: Option<String>,
+    pub host: Option<String>,
+    pub port: Option<u32>,
+    pub min_players: u32,
+}
+
+pub struct LiveStatus {
+    pub guild_id: u64,
+    pub channel_id: u64,
+    pub message_id: u64,
+    pub update_interval: u32,
+    pub all_bookmarks: bool,
+}
+
+// Bookmark import/export file. Version 2 added alert settings, older files still import.
+pub const BOOKMARK_EXPORT_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize)]
+pub struct BookmarkExport {
+    pub version: u32,
+    pub bookmarks: Vec<ExportedBookmark>,
+    #[serde(default)]
+    pub groups: Vec<ExportedGroup>,
+    #[serde(default)]
+    pub settings: ExportedSettings,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ExportedBookmark {
+    pub name: String,
+    pub host: String,
+    pub port: u32,
+    pub description: Option<String>,
+    pub emoji: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ExportedGroup {
+    pub name: String,
+    pub bookmarks: Vec<String>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct ExportedSettings {
+    pub infocmds_required_role: Option<u64>,
+    // Missing from version 1 files, which leaves the guild's alert settings alone
+    #[serde(default)]
+    pub alerts: Option<ExportedAlertSettings>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct ExportedAlertSettings {
+    pub availability_channel: Option<u64>,
+    pub utc_offset: i32,
+    pub quiet_start: Option<u8>,
+    pub quiet_end: Option<u8>,
+    pub digest_minutes: u32,
 }
 
 // API handling
@@ -156,6 +245,57 @@ pub async fn resolve_ip(initial: String) -> Option<String> {
     }
 }
 
+// Split pasted addresses like `1.2.3.4:28785`, `/connect host port` or `sauerbraten://host:port`
+pub fn parse_address(input: &str, port: Option<u32>) -> Result<(String, u32), Error> {
+    let mut address = input.trim();
+
+    // Strip the connect command or URL scheme
+    for prefix in ["/connect ", "connect ", "sauerbraten://", "sauer://"] {
+        if address.get(..prefix.len()).is_some_and(|start| start.eq_ignore_ascii_case(prefix)) {
+            address = address[prefix.len()..].trim();
+        }
+    }
+    let address = address.trim_end_matches('/');
+
+    // Either `host port` or `host:port`, where IPv6 hosts are bracketed if a port follows
+    let (host, embedded_port) = match address.split_whitespace().collect::<Vec<&str>>()[..] {
+        [host] => match host.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+            Some((host, "")) => (host, None),
+            Some((host, rest)) => match rest.strip_prefix(':') {
+                Some(port) => (host, Some(port)),
+                None => return Err(format!("Unable to read a server address from \"{input}\"!").into()),
+            },
+            None if host.matches(':').count() > 1 => (host, None),
+            None => match host.rsplit_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (host, None),
+            },
+        },
+        [host, port] => (host.trim_start_matches('[').trim_end_matches(']'), Some(port)),
+        _ => return Err(format!("Unable to read a server address from \"{input}\"!").into()),
+    };
+
+    if host.is_empty() {
+        return Err(format!("Unable to read a server address from \"{input}\"!").into());
+    }
+
+    // An explicitly given port takes priority over one in the address
+    let port = match (port, embedded_port) {
+        (Some(port), _) => port,
+        (None, Some(port)) => match port.parse::<u32>() {
+            Ok(port) => port,
+            Err(_) => return Err(format!("\"{port}\" is not a valid port!").into()),
+        },
+        (None, None) => 28785,
+    };
+
+    if port == 0 || port > 65535 {
+        return Err(format!("\"{port}\" is not a valid port!").into());
+    }
+
+    Ok((host.to_string(), port))
+}
+
 pub async fn grab_api_data(client: &reqwest::Client, api_url: String, backup_url: &String) -> Result<Value, Error> {
     let init_request = client.get(&api_url).send().await;
 
@@ -185,6 +325,47 @@ pub async fn grab_api_data(client: &reqwest::Client, api_url: String, backup_url
     }
 }
 
+// Minutes ahead of UTC from offsets like `+2`, `-5:30` or `UTC+05:45`
+pub fn parse_utc_offset(input: &str) -> Result<i32, Error> {
+    let invalid = || format!("\"{}\" isn't a UTC offset! Use something like +2 or -5:30.", input.trim());
+
+    let mut offset = input.trim();
+    for prefix in ["utc", "gmt"] {
+        if offset.get(..prefix.len()).is_some_and(|start| start.eq_ignore_ascii_case(prefix)) {
+            offset = offset[prefix.len()..].trim();
+        }
+    }
+    if offset.is_empty() {
+        return Ok(0);
+    }
+
+    let (sign, offset) = match (offset.strip_prefix('+'), offset.strip_prefix('-')) {
+        (Some(rest), _) => (1, rest),
+        (_, Some(rest)) => (-1, rest),
+        _ => (1, offset)
+    };
+    let (hours, minutes) = offset.split_once(':').unwrap_or((offset, "0"));
+    let hours: u32 = hours.trim().parse().map_err(|_| invalid())?;
+    let minutes: u32 = minutes.trim().parse().map_err(|_| invalid())?;
+
+    if !(0..60).contains(&minutes) || hours > 14 {
+        return Err(invalid().into());
+    }
+
+    // Real offsets run from UTC-12:00 to UTC+14:00
+    let offset = sign * (hours * 60 + minutes) as i32;
+    if !(-12 * 60..=14 * 60).contains(&offset) {
+        return Err(invalid().into());
+    }
+
+    Ok(offset)
+}
+
+pub fn format_utc_offset(offset: i32) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    format!("UTC{}{}:{:02}", sign, offset.abs() / 60, offset.abs() % 60)
+}
+
 // Don't format for Discord markdown
 pub fn escape_markdown(mut text: String) -> String {
     text = text.replace('*', "\\*");
@@ -195,85 +376,492 @@ pub fn escape_markdown(mut text: String) -> String {
     text
 }
 
-// Modified sample paginate code for the user list
-pub async fn paginate<U, E>(
+// A game mode from MODENAMES, parsed from its name or a common short form
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct GameMode(&'static str);
+
+impl GameMode {
+    pub fn name(&self) -> &'static str {
+        self.0
+    }
+}
+
+impl std::str::FromStr for GameMode {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self, Error> {
+        let normalized = input.trim().to_lowercase().replace([' ', '-'], "_");
+        let name = match normalized.as_str() {
+            "coop" | "edit" => "coop_edit",
+            "insta" => "instagib",
+            "effic" => "efficiency",
+            "tac" => "tactics",
+            "regen" => "regen_capture",
+            "ictf" => "insta_ctf",
+            "ectf" => "effic_ctf",
+            other => other
+        };
+
+        // Underscores are optional, so "instactf" is insta_ctf
+        MODENAMES.iter()
+            .find(|mode| **mode == name || mode.replace('_', "") == name.replace('_', ""))
+            .map(|mode| GameMode(mode))
+            .ok_or_else(|| format!("{} isn't a game mode!", input.trim()).into())
+    }
+}
+
+impl std::fmt::Display for GameMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+// Name without Cube colour codes (\f or ^f followed by a code), lowercased for matching
+pub fn plain_name(name: &str) -> String {
+    let mut plain = String::new();
+    let mut chars = name.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\x0c' {
+            chars.next();
+        } else if c == '^' && chars.peek() == Some(&'f') {
+            chars.next();
+            chars.next();
+        } else {
+            plain.push(c);
+        }
+    }
+
+    plain.trim().to_lowercase()
+}
+
+// Clantags are worn as either a name prefix or suffix
+pub fn has_clantag(name: &str, tag: &str) -> bool {
+    let name = name.to_lowercase();
+    let tag = tag.to_lowercase();
+
+    name.starts_with(&tag) || name.ends_with(&tag)
+}
+
+// Levenshtein distance, used for fuzzy name matching
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b_chars: Vec<char> = b.chars().collect();
+    let mut prev_row: Vec<usize> = (0..=b_chars.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut row = vec![i + 1; b_chars.len() + 1];
+        for (j, b_char) in b_chars.iter().enumerate() {
+            let cost = if a_char == *b_char { 0 } else { 1 };
+            row[j + 1] = (prev_row[j] + cost).min(prev_row[j + 1] + 1).min(row[j] + 1);
+        }
+        prev_row = row;
+    }
+
+    prev_row[b_chars.len()]
+}
+
+// Rank items by how well their key matches partial input: prefix, then substring, then edit distance
+pub fn fuzzy_rank<T>(partial: &str, items: Vec<T>, key: impl Fn(&T) -> &str) -> Vec<T> {
+    let partial = partial.to_lowercase();
+    let max_distance = (partial.chars().count() / 3).max(1);
+
+    let mut ranked: Vec<(usize, String, T)> = items.into_iter()
+        .filter_map(|item| {
+            let name = key(&item).to_lowercase();
+            let score = if name.starts_with(&partial) {
+                0
+            } else if name.contains(&partial) {
+                1
+            } else {
+                // Compare against the start of the name so short input can still match long names
+                let start: String = name.chars().take(partial.chars().count()).collect();
+                let distance = edit_distance(&start, &partial);
+                if distance > max_distance {
+                    return None;
+                }
+
+                distance + 1
+            };
+
+            Some((score, name, item))
+        })
+        .collect();
+
+    ranked.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)));
+    ranked.into_iter().take(25).map(|(_, _, item)| item).collect()
+}
+
+// Reuse autocomplete results for a while so typing doesn't spam the tracker
+pub async fn cached_autocomplete(
+    data: &Data,
+    key: String,
+    fetch: impl std::future::Future<Output = Result<Vec<String>, Error>>,
+) -> Vec<String> {
+    if let Some((fetched_at, names)) = data.autocomplete_cache.lock().await.get(&key) {
+        if fetched_at.elapsed() < AUTOCOMPLETE_TTL {
+            return names.clone();
+        }
+    }
+
+    let names = match fetch.await {
+        Ok(names) => names,
+        Err(_) => return Vec::new()
+    };
+
+    let mut cache = data.autocomplete_cache.lock().await;
+    cache.retain(|_, (fetched_at, _)| fetched_at.elapsed() < AUTOCOMPLETE_TTL);
+    cache.insert(key, (std::time::Instant::now(), names.clone()));
+
+    names
+}
+
+// Cached results for shorter input narrowed down to names that still match. Typing more
+// only narrows a lookup, so each keystroke doesn't need to ask the tracker again. Results
+// with `limit` or more names might have been cut short by the tracker and aren't narrowed.
+pub async fn cached_narrowed(data: &Data, kind: &str, partial: &str, limit: usize) -> Option<Vec<String>> {
+    let lowered = partial.to_lowercase();
+    let cache = data.autocomplete_cache.lock().await;
+
+    let mut ends: Vec<usize> = lowered.char_indices().map(|(i, _)| i).skip(1).collect();
+    ends.push(lowered.len());
+    for end in ends.into_iter().rev() {
+        if let Some((fetched_at, names)) = cache.get(&format!("{kind}:{}", &lowered[..end])) {
+            if fetched_at.elapsed() < AUTOCOMPLETE_TTL && names.len() < limit {
+                return Some(names.iter().filter(|name| name.to_lowercase().contains(&lowered)).cloned().collect());
+            }
+        }
+    }
+
+    None
+}
+
+// Value of another option the user has already filled in, for autocomplete
+pub fn option_value<U, E>(ctx: Context<'_, U, E>, name: &str) -> Option<String> {
+    let poise::Context::Application(ctx) = ctx else {
+        return None;
+    };
+
+    ctx.interaction.data.options.iter()
+        .find(|option| option.name == name)
+        .and_then(|option| match &option.value {
+            serenity::CommandDataOptionValue::String(value) => Some(value.clone()),
+            serenity::CommandDataOptionValue::Integer(value) => Some(value.to_string()),
+            _ => None
+        })
+}
+
+#[derive(poise::Modal)]
+#[name = "Jump to page"]
+struct JumpModal {
+    #[name = "Page number"]
+    #[min_length = 1]
+    #[max_length = 4]
+    page: String,
+}
+
+// Paginate embeds with first/prev/next/last buttons and a jump to page modal
+pub async fn paginate<U: Send + Sync, E>(
     ctx: Context<'_, U, E>,
-    title: String,
-    pages: &[&str],
-    embed_url: Option<String>,
+    pages: Vec<serenity::CreateEmbed>,
+    owner_only: bool,
 ) -> Result<(), serenity::Error> {
+    if pages.is_empty() {
+        return Ok(());
+    }
+
     // Define some unique identifiers for the navigation buttons
     let ctx_id = ctx.id();
+    let first_button_id = format!("{}first", ctx_id);
     let prev_button_id = format!("{}prev", ctx_id);
     let next_button_id = format!("{}next", ctx_id);
+    let last_button_id = format!("{}last", ctx_id);
+    let jump_button_id = format!("{}jump", ctx_id);
 
-    // Send the embed with the first page as content
-    let reply = {
-        let components = serenity::CreateActionRow::Buttons(vec![
-            serenity::CreateButton::new(&prev_button_id).emoji('◀'),
-            serenity::CreateButton::new(&next_button_id).emoji('▶'),
-        ]);
-
-        poise::CreateReply::default()
-            .embed(serenity::CreateEmbed::default().description(pages[0]).title(&title).url(embed_url.clone().unwrap_or_else(String::new)))
-            .components(vec![components])
+    let page_embed = |page: usize| {
+        pages[page].clone().footer(serenity::CreateEmbedFooter::new(format!("Page {}/{}", page + 1, pages.len())))
+    };
+
+    let page_components = |page: usize, disabled: bool| {
+        vec![serenity::CreateActionRow::Buttons(vec![
+            serenity::CreateButton::new(&first_button_id).emoji('⏮').disabled(disabled || page == 0),
+            serenity::CreateButton::new(&prev_button_id).emoji('◀').disabled(disabled || page == 0),
+            serenity::CreateButton::new(&jump_button_id).label("Jump").style(serenity::ButtonStyle::Secondary).disabled(disabled),
+            serenity::CreateButton::new(&next_button_id).emoji('▶').disabled(disabled || page == pages.len() - 1),
+            serenity::CreateButton::new(&last_button_id).emoji('⏭').disabled(disabled || page == pages.len() - 1),
+        ])]
     };
 
-    ctx.send(reply).await?;
+    // A single page doesn't need navigating
+    if pages.len() == 1 {
+        ctx.send(poise::CreateReply::default().embed(page_embed(0))).await?;
+        return Ok(());
+    }
 
     // Send the embed with the first page as content
-    let mut current_page = 0;
+    let reply = ctx.send(poise::CreateReply::default()
+        .embed(page_embed(0))
+        .components(page_components(0, false))
+    ).await?;
+
+    let author_id = ctx.author().id;
+    let mut current_page: usize = 0;
 
     // Loop through incoming interactions with the navigation buttons
     while let Some(press) = serenity::collector::ComponentInteractionCollector::new(ctx)
         // We defined our button IDs to start with `ctx_id`. If they don't, some other command's
         // button was pressed
         .filter(move |press| press.data.custom_id.starts_with(&ctx_id.to_string()))
-        // Timeout when no navigation button has been pressed for 24 hours
-        .timeout(std::time::Duration::from_secs(3600 * 24))
+        // Timeout when no navigation button has been pressed for 10 minutes
+        .timeout(std::time::Duration::from_secs(600))
         .await
     {
-        // Depending on which button was pressed, go to next or previous page
-        if press.data.custom_id == next_button_id {
-            current_page += 1;
-            if current_page >= pages.len() {
-                current_page = 0;
-            }
+        if owner_only && press.user.id != author_id {
+            press
+                .create_response(
+                    ctx.serenity_context(),
+                    serenity::CreateInteractionResponse::Message(
+                        serenity::CreateInteractionResponseMessage::new()
+                            .content(format!("Only <@{}> can change pages here!", author_id))
+                            .ephemeral(true),
+                    ),
+                )
+                .await?;
+            continue;
+        }
+
+        // Depending on which button was pressed, go to the chosen page
+        if press.data.custom_id == first_button_id {
+            current_page = 0;
         } else if press.data.custom_id == prev_button_id {
-            current_page = current_page.checked_sub(1).unwrap_or(pages.len() - 1);
+            current_page = current_page.saturating_sub(1);
+        } else if press.data.custom_id == next_button_id {
+            current_page = (current_page + 1).min(pages.len() - 1);
+        } else if press.data.custom_id == last_button_id {
+            current_page = pages.len() - 1;
+        } else if press.data.custom_id == jump_button_id {
+            // The modal takes the place of the interaction response, so edit the message directly
+            let jump = poise::execute_modal_on_component_interaction::<JumpModal>(
+                ctx,
+                press.clone(),
+                None,
+                Some(std::time::Duration::from_secs(60)),
+            )
+            .await?;
+
+            if let Some(page) = jump.and_then(|jump| jump.page.trim().parse::<usize>().ok()) {
+                current_page = page.clamp(1, pages.len()) - 1;
+                reply.edit(ctx, poise::CreateReply::default()
+                    .embed(page_embed(current_page))
+                    .components(page_components(current_page, false))
+                ).await?;
+            }
+
+            continue;
         } else {
             // This is an unrelated button interaction
             continue;
         }
 
         // Update the message with the new page contents
-        /*press
-            .create_interaction_response(ctx, |b| {
-                b.kind(serenity::InteractionResponseType::UpdateMessage)
-                    .interaction_response_data(|b| {
-                        b.embed(|b| {
-                            b.description(pages[current_page]);
-
-                            if embed_url.clone().is_some() {
-                                b.url(embed_url.clone().unwrap());
-                            }
-
-                            b.title(&title)
-                        })
-                    })
-            })
-            .await?;*/
-
         press
             .create_response(
                 ctx.serenity_context(),
                 serenity::CreateInteractionResponse::UpdateMessage(
                     serenity::CreateInteractionResponseMessage::new()
-                        .embed(serenity::CreateEmbed::new().description(pages[current_page]).title(&title).url(embed_url.clone().unwrap_or_else(String::new))),
+                        .embed(page_embed(current_page))
+                        .components(page_components(current_page, false)),
                 ),
             )
             .await?;
     }
 
+    // Disable the buttons once nothing is listening for them
+    reply.edit(ctx, poise::CreateReply::default()
+        .embed(page_embed(current_page))
+        .components(page_components(current_page, true))
+    ).await?;
+
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_names_have_no_distance() {
+        assert_eq!(edit_distance("Frosty", "Frosty"), 0);
+        assert_eq!(edit_distance("", ""), 0);
+    }
+
+    #[test]
+    fn distance_counts_single_edits() {
+        assert_eq!(edit_distance("frosty", "frostt"), 1);
+        assert_eq!(edit_distance("frosty", "frost"), 1);
+        assert_eq!(edit_distance("frost", "frosty"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn distance_to_empty_is_length() {
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("abc", ""), 3);
+    }
+
+    #[test]
+    fn distance_counts_characters_not_bytes() {
+        assert_eq!(edit_distance("häck", "hack"), 1);
+    }
+
+    fn address(input: &str) -> (String, u32) {
+        parse_address(input, None).unwrap()
+    }
+
+    #[test]
+    fn host_and_port_forms() {
+        assert_eq!(address("1.2.3.4:28785"), (String::from("1.2.3.4"), 28785));
+        assert_eq!(address("1.2.3.4 28786"), (String::from("1.2.3.4"), 28786));
+        assert_eq!(address("/connect example.org 10000"), (String::from("example.org"), 10000));
+        assert_eq!(address("sauerbraten://example.org:10000/"), (String::from("example.org"), 10000));
+    }
+
+    #[test]
+    fn bare_host_uses_default_port() {
+        assert_eq!(address("example.org"), (String::from("example.org"), 28785));
+        assert_eq!(address("  Connect example.org  "), (String::from("example.org"), 28785));
+    }
+
+    #[test]
+    fn given_port_overrides_embedded_one() {
+        assert_eq!(parse_address("example.org:1000", Some(2000)).unwrap(), (String::from("example.org"), 2000));
+    }
+
+    #[test]
+    fn bad_ports_are_rejected() {
+        assert!(parse_address("example.org:abc", None).is_err());
+        assert!(parse_address("example.org:0", None).is_err());
+        assert!(parse_address("example.org:65536", None).is_err());
+        assert!(parse_address("example.org 1 2", None).is_err());
+        assert!(parse_address(":28785", None).is_err());
+        assert!(parse_address("", None).is_err());
+    }
+
+    #[test]
+    fn ipv6_addresses() {
+        assert_eq!(address("[2001:db8::1]:28785"), (String::from("2001:db8::1"), 28785));
+        assert_eq!(address("[2001:db8::1]"), (String::from("2001:db8::1"), 28785));
+        assert_eq!(address("2001:db8::1"), (String::from("2001:db8::1"), 28785));
+        assert_eq!(address("/connect 2001:db8::1 10000"), (String::from("2001:db8::1"), 10000));
+        assert_eq!(address("[2001:db8::1] 10000"), (String::from("2001:db8::1"), 10000));
+        assert!(parse_address("[2001:db8::1]x", None).is_err());
+        assert!(parse_address("[2001:db8::1]:abc", None).is_err());
+    }
+
+    #[test]
+    fn utc_offsets_in_hours_and_minutes() {
+        assert_eq!(parse_utc_offset("+2").unwrap(), 120);
+        assert_eq!(parse_utc_offset("2").unwrap(), 120);
+        assert_eq!(parse_utc_offset("-5:30").unwrap(), -330);
+        assert_eq!(parse_utc_offset(" +5:45 ").unwrap(), 345);
+        assert_eq!(parse_utc_offset("-0:30").unwrap(), -30);
+        assert_eq!(parse_utc_offset("+14:00").unwrap(), 840);
+        assert_eq!(parse_utc_offset("-12").unwrap(), -720);
+    }
+
+    #[test]
+    fn utc_offsets_with_a_prefix() {
+        assert_eq!(parse_utc_offset("UTC+3").unwrap(), 180);
+        assert_eq!(parse_utc_offset("gmt -4").unwrap(), -240);
+        assert_eq!(parse_utc_offset("UTC").unwrap(), 0);
+        assert_eq!(parse_utc_offset("").unwrap(), 0);
+    }
+
+    #[test]
+    fn impossible_utc_offsets_are_rejected() {
+        assert!(parse_utc_offset("+15").is_err());
+        assert!(parse_utc_offset("-13").is_err());
+        assert!(parse_utc_offset("+14:30").is_err());
+        assert!(parse_utc_offset("-12:45").is_err());
+        assert!(parse_utc_offset("+2:60").is_err());
+        assert!(parse_utc_offset("+").is_err());
+        assert!(parse_utc_offset("two").is_err());
+        assert!(parse_utc_offset("é").is_err());
+    }
+
+    fn mode(input: &str) -> &'static str {
+        input.parse::<GameMode>().unwrap().name()
+    }
+
+    #[test]
+    fn mode_names_parse_as_themselves() {
+        for name in MODENAMES {
+            assert_eq!(mode(name), name);
+        }
+    }
+
+    #[test]
+    fn modes_ignore_case_spaces_and_dashes() {
+        assert_eq!(mode(" Insta CTF "), "insta_ctf");
+        assert_eq!(mode("effic-hold"), "effic_hold");
+        assert_eq!(mode("instactf"), "insta_ctf");
+        assert_eq!(mode("REGENCAPTURE"), "regen_capture");
+    }
+
+    #[test]
+    fn short_mode_names() {
+        assert_eq!(mode("coop"), "coop_edit");
+        assert_eq!(mode("edit"), "coop_edit");
+        assert_eq!(mode("insta"), "instagib");
+        assert_eq!(mode("effic"), "efficiency");
+        assert_eq!(mode("tac"), "tactics");
+        assert_eq!(mode("regen"), "regen_capture");
+        assert_eq!(mode("ictf"), "insta_ctf");
+        assert_eq!(mode("ectf"), "effic_ctf");
+    }
+
+    #[test]
+    fn unknown_modes_are_rejected() {
+        assert!("".parse::<GameMode>().is_err());
+        assert!("deathmatch".parse::<GameMode>().is_err());
+        assert!("insta ctf2".parse::<GameMode>().is_err());
+    }
+
+    #[test]
+    fn utc_offsets_format_back() {
+        assert_eq!(format_utc_offset(0), "UTC+0:00");
+        assert_eq!(format_utc_offset(-330), "UTC-5:30");
+        assert_eq!(format_utc_offset(345), "UTC+5:45");
+        assert_eq!(format_utc_offset(parse_utc_offset("-0:30").unwrap()), "UTC-0:30");
+    }
+
+    fn rank(partial: &str, names: &[&str]) -> Vec<String> {
+        let names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+        fuzzy_rank(partial, names, |name| name.as_str())
+    }
+
+    #[test]
+    fn prefix_ranks_before_substring() {
+        assert_eq!(rank("pub", &["mypub", "Public", "pubstomp"]), vec!["Public", "pubstomp", "mypub"]);
+    }
+
+    #[test]
+    fn typos_rank_after_substrings() {
+        assert_eq!(rank("duel", &["dual server", "the duel"]), vec!["the duel", "dual server"]);
+    }
+
+    #[test]
+    fn distant_names_are_dropped() {
+        assert!(rank("duel", &["insta", "ctf"]).is_empty());
+    }
+
+    #[test]
+    fn empty_input_lists_everything_alphabetically() {
+        assert_eq!(rank("", &["b", "A", "c"]), vec!["A", "b", "c"]);
+    }
+
+    #[test]
+    fn results_are_capped_at_25() {
+        let names: Vec<String> = (0..40).map(|i| format!("server {i}")).collect();
+        let names: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
+        assert_eq!(rank("server", &names).len(), 25);
+    }
+}
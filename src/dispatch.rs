@@ -0,0 +1,462 @@
+use crate::Error;
+use crate::alerts::AlertTarget;
+use crate::livestatus::http_status;
+use chrono::Timelike;
+use poise::serenity_prelude as serenity;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// How often queued alerts are looked at
+const DISPATCH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+// Minutes an event's key is remembered, so it only reaches a target once
+const DEDUP_WINDOW: u32 = 15;
+
+// Messages a target may get per RATE_LIMIT_WINDOW minutes. Alerts over the limit wait
+// and go out together as a digest once the target is allowed another message.
+const RATE_LIMIT_WINDOW: u32 = 10;
+const CHANNEL_RATE_LIMIT: i64 = 5;
+const USER_RATE_LIMIT: i64 = 3;
+
+// Alerts a target can have waiting before the oldest are dropped, and the longest any
+// alert waits. Long enough for a day of quiet hours or digests.
+const MAX_BACKLOG: usize = 50;
+const MAX_BACKLOG_HOURS: u32 = 48;
+
+// Sends tried before a message is given up on, waiting a minute longer after each failure
+const MAX_DELIVERY_ATTEMPTS: u32 = 5;
+
+// Alerts listed in a digest before the rest are only counted, and how long each line can be
+const DIGEST_LINES: usize = 20;
+const DIGEST_LINE_LENGTH: usize = 150;
+
+// An alert on its way to a channel or a user's DMs
+pub struct Notification {
+    pub target: AlertTarget,
+    // Guild whose quiet hours and digest setting apply to channel alerts
+    pub guild_id: Option<u64>,
+    // Identifies the event, alerts with a key the target recently got are dropped
+    pub key: String,
+    // One line standing in for the message in digests
+    pub summary: String,
+    pub message: serenity::CreateMessage,
+}
+
+// Queue an alert for the dispatcher unless its target recently got the same event. Returns
+// whether the alert is taken care of, which is only false when it couldn't be stored.
+pub async fn notify(database: &sqlx::MySqlPool, notification: Notification) -> bool {
+    match queue_notification(database, notification).await {
+        Ok(()) => true,
+        Err(e) => {
+            println!("[ ERROR ] Queueing alert failed: {e}");
+            false
+        }
+    }
+}
+
+async fn queue_notification(database: &sqlx::MySqlPool, notification: Notification) -> Result<(), Error> {
+    let (kind, target_id) = notification.target.parts();
+    let key: String = format!("{kind}:{target_id}:{}", notification.key).chars().take(255).collect();
+    let guild_id = match notification.target {
+        AlertTarget::Channel(_) => notification.guild_id,
+        AlertTarget::User(_) => None
+    };
+    let payload = serde_json::to_string(&notification.message)?;
+
+    let mut tx = database.begin().await?;
+
+    // A new key is inserted and an expired one renewed, anything else is a duplicate. sqlx
+    // counts matched rather than changed rows, so ON DUPLICATE KEY UPDATE can't tell these apart.
+    let inserted = sqlx::query!("INSERT IGNORE INTO notification_keys (dedup_key, created_at) VALUES (?, NOW())", key)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    let renewed = if inserted == 0 {
+        sqlx::query!("UPDATE notification_keys SET created_at = NOW() WHERE dedup_key = ? AND created_at < NOW() - INTERVAL ? MINUTE", key, DEDUP_WINDOW)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected()
+    } else {
+        0
+    };
+    if inserted == 0 && renewed == 0 {
+        return Ok(());
+    }
+
+    sqlx::query!("INSERT INTO notification_queue (target_kind, target_id, guild_id, summary, payload, queued_at) VALUES (?, ?, ?, ?, ?, NOW())",
+        kind, target_id, guild_id, notification.summary, payload)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+struct QueuedNotification {
+    id: i32,
+    target: AlertTarget,
+    guild_id: Option<u64>,
+    summary: String,
+    payload: String,
+    age: i64,
+    attempts: u32,
+    // Waiting to be retried after a failed send
+    backing_off: bool,
+}
+
+// A guild's quiet hours, in its own time, and digest window
+#[derive(Clone, Copy, Default)]
+struct AlertSchedule {
+    utc_offset: i32,
+    quiet_start: Option<u8>,
+    quiet_end: Option<u8>,
+    digest_minutes: u32,
+}
+
+// Deliver queued alerts to each target that isn't in quiet hours, collecting a digest
+// or over its rate limit. Alerts go out one by one while the rate limit allows it.
+pub async fn run(http: Arc<serenity::Http>, database: sqlx::MySqlPool) {
+    let mut interval = tokio::time::interval(DISPATCH_INTERVAL);
+
+    loop {
+        interval.tick().await;
+        dispatch(&http, &database).await;
+    }
+}
+
+async fn dispatch(http: &serenity::Http, database: &sqlx::MySqlPool) {
+    let pruned = [
+        sqlx::query!("DELETE FROM notification_keys WHERE created_at < NOW() - INTERVAL ? MINUTE", DEDUP_WINDOW)
+            .execute(database)
+            .await,
+        sqlx::query!("DELETE FROM notification_log WHERE sent_at < NOW() - INTERVAL ? MINUTE", RATE_LIMIT_WINDOW)
+            .execute(database)
+            .await,
+        sqlx::query!("DELETE FROM notification_queue WHERE queued_at < NOW() - INTERVAL ? HOUR", MAX_BACKLOG_HOURS)
+            .execute(database)
+            .await,
+    ];
+    for res in pruned {
+        if let Err(e) = res {
+            println!("[ ERROR ] Pruning alert state failed: {e}");
+        }
+    }
+
+    let queued: Vec<QueuedNotification> = match sqlx::query!("SELECT id, target_kind, target_id, guild_id, summary, payload,
+        TIMESTAMPDIFF(SECOND, queued_at, NOW()) AS `age!: i64`, attempts,
+        (next_attempt IS NOT NULL AND next_attempt > NOW()) AS `backing_off!: bool`
+        FROM notification_queue ORDER BY id")
+        .fetch_all(database)
+        .await
+    {
+        Ok(rows) => rows.into_iter()
+            .filter_map(|row| Some(QueuedNotification {
+                id: row.id,
+                target: AlertTarget::from_parts(&row.target_kind, row.target_id)?,
+                guild_id: row.guild_id,
+                summary: row.summary,
+                payload: row.payload,
+                age: row.age,
+                attempts: row.attempts,
+                backing_off: row.backing_off,
+            }))
+            .collect(),
+        Err(e) => {
+            println!("[ ERROR ] Reading queued alerts failed: {e}");
+            return;
+        }
+    };
+    if queued.is_empty() {
+        return;
+    }
+
+    let schedules: HashMap<u64, AlertSchedule> = sqlx::query!("SELECT guild_id, utc_offset, quiet_start, quiet_end, digest_minutes FROM guild_settings
+        WHERE guild_id IN (SELECT guild_id FROM notification_queue)")
+        .fetch_all(database)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|row| (row.guild_id, AlertSchedule {
+            utc_offset: row.utc_offset,
+            quiet_start: row.quiet_start,
+            quiet_end: row.quiet_end,
+            digest_minutes: row.digest_minutes,
+        }))
+        .collect();
+
+    let sent: HashMap<(String, u64), i64> = sqlx::query!("SELECT target_kind, target_id, COUNT(*) AS `sent!: i64` FROM notification_log
+        WHERE sent_at > NOW() - INTERVAL ? MINUTE GROUP BY target_kind, target_id", RATE_LIMIT_WINDOW)
+        .fetch_all(database)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|row| ((row.target_kind, row.target_id), row.sent))
+        .collect();
+
+    // Alerts for each target, oldest first
+    let mut targets: Vec<(AlertTarget, Vec<QueuedNotification>)> = Vec::new();
+    for notification in queued {
+        match targets.iter_mut().find(|(target, _)| *target == notification.target) {
+            Some((_, notifications)) => notifications.push(notification),
+            None => targets.push((notification.target, vec![notification]))
+        }
+    }
+
+    for (target, mut notifications) in targets {
+        let (kind, target_id) = target.parts();
+
+        // Keep only the newest alerts of a long backlog
+        if notifications.len() > MAX_BACKLOG {
+            let dropped = notifications.len() - MAX_BACKLOG;
+            let dropped_before = notifications[dropped].id;
+            let res = sqlx::query!("DELETE FROM notification_queue WHERE target_kind = ? AND target_id = ? AND id < ?", kind, target_id, dropped_before)
+                .execute(database)
+                .await;
+            if let Err(e) = res {
+                println!("[ ERROR ] Dropping old alerts for {target:?} failed: {e}");
+                continue;
+            }
+            notifications.drain(..dropped);
+        }
+
+        if notifications.iter().any(|notification| notification.backing_off) {
+            continue;
+        }
+
+        // Channel alerts all come from the channel's guild
+        let schedule = notifications.iter()
+            .find_map(|notification| notification.guild_id)
+            .and_then(|guild_id| schedules.get(&guild_id).copied())
+            .unwrap_or_default();
+        // Alerts that waited through quiet hours go out together once they end
+        let now = chrono::Utc::now();
+        if in_quiet_hours(now, schedule.utc_offset, schedule.quiet_start, schedule.quiet_end) {
+            continue;
+        }
+        let held_for_quiet_hours = in_quiet_hours(now - chrono::Duration::seconds(notifications[0].age), schedule.utc_offset, schedule.quiet_start, schedule.quiet_end);
+        if notifications[0].age < schedule.digest_minutes as i64 * 60 {
+            continue;
+        }
+
+        let limit = match target {
+            AlertTarget::Channel(_) => CHANNEL_RATE_LIMIT,
+            AlertTarget::User(_) => USER_RATE_LIMIT
+        };
+        let allowance = limit - sent.get(&(kind.to_string(), target_id)).copied().unwrap_or(0);
+        if allowance <= 0 {
+            continue;
+        }
+
+        // Alerts are sent one by one, except in digest mode, after quiet hours or when more
+        // are waiting than the rate limit allows right now. Those are sent as one digest.
+        let batches: Vec<&[QueuedNotification]> = if schedule.digest_minutes == 0 && !held_for_quiet_hours && notifications.len() as i64 <= allowance {
+            notifications.chunks(1).collect()
+        } else {
+            vec![&notifications[..]]
+        };
+
+        for batch in batches {
+            let (first_id, last_id) = (batch[0].id, batch[batch.len() - 1].id);
+            let payload = if batch.len() == 1 {
+                match serde_json::from_str(&batch[0].payload) {
+                    Ok(payload) => payload,
+                    Err(e) => {
+                        println!("[ ERROR ] Dropping unreadable alert for {target:?}: {e}");
+                        remove_queued(database, target, first_id, last_id).await;
+                        continue;
+                    }
+                }
+            } else {
+                build_digest(batch)
+            };
+
+            // A failed send is retried later unless the target is gone or out of attempts.
+            // Alerts after it wait so they don't arrive out of order.
+            if let Err(e) = deliver(http, target, &payload).await {
+                let attempts = batch.iter().map(|notification| notification.attempts).max().unwrap_or(0) + 1;
+                println!("[ ERROR ] Sending alert to {target:?} failed (attempt {attempts}): {e}");
+
+                if matches!(http_status(&e), Some(403 | 404)) {
+                    remove_queued(database, target, first_id, notifications[notifications.len() - 1].id).await;
+                } else if attempts >= MAX_DELIVERY_ATTEMPTS {
+                    remove_queued(database, target, first_id, last_id).await;
+                } else {
+                    let res = sqlx::query!("UPDATE notification_queue SET attempts = ?, next_attempt = NOW() + INTERVAL ? MINUTE WHERE target_kind = ? AND target_id = ? AND id BETWEEN ? AND ?",
+                        attempts, attempts, kind, target_id, first_id, last_id)
+                        .execute(database)
+                        .await;
+                    if let Err(e) = res {
+                        println!("[ ERROR ] Scheduling alert retry for {target:?} failed: {e}");
+                    }
+                }
+                break;
+            }
+
+            remove_queued(database, target, first_id, last_id).await;
+
+            let res = sqlx::query!("INSERT INTO notification_log (target_kind, target_id, sent_at) VALUES (?, ?, NOW())", kind, target_id)
+                .execute(database)
+                .await;
+            if let Err(e) = res {
+                println!("[ ERROR ] Logging alert to {target:?} failed: {e}");
+            }
+        }
+    }
+}
+
+// Take a target's alerts from first_id to last_id off the queue
+async fn remove_queued(database: &sqlx::MySqlPool, target: AlertTarget, first_id: i32, last_id: i32) {
+    let (kind, target_id) = target.parts();
+    let res = sqlx::query!("DELETE FROM notification_queue WHERE target_kind = ? AND target_id = ? AND id BETWEEN ? AND ?", kind, target_id, first_id, last_id)
+        .execute(database)
+        .await;
+    if let Err(e) = res {
+        println!("[ ERROR ] Removing alerts for {target:?} failed: {e}");
+    }
+}
+
+// Whether the guild's local hour falls in its quiet hours, which can wrap past midnight
+fn in_quiet_hours(now: chrono::DateTime<chrono::Utc>, utc_offset: i32, quiet_start: Option<u8>, quiet_end: Option<u8>) -> bool {
+    let (start, end) = match (quiet_start, quiet_end) {
+        (Some(start), Some(end)) if start != end => (start as u32, end as u32),
+        _ => return false
+    };
+
+    let hour = (now + chrono::Duration::minutes(utc_offset as i64)).hour();
+    if start < end {
+        hour >= start && hour < end
+    } else {
+        hour >= start || hour < end
+    }
+}
+
+// One message listing every alert that was held back
+fn build_digest(notifications: &[QueuedNotification]) -> serde_json::Value {
+    let mut desc = notifications.iter()
+        .take(DIGEST_LINES)
+        .map(|notification| format!("- {}", notification.summary.chars().take(DIGEST_LINE_LENGTH).collect::<String>()))
+        .collect::<Vec<String>>()
+        .join("\n");
+    if notifications.len() > DIGEST_LINES {
+        desc = format!("{desc}\n*...and {} more.*", notifications.len() - DIGEST_LINES);
+    }
+
+    let embed = serenity::CreateEmbed::new()
+        .colour(0xFF0000)
+        .title(format!("{} alerts", notifications.len()))
+        .description(desc);
+
+    serde_json::to_value(serenity::CreateMessage::new().embed(embed)).unwrap()
+}
+
+// Post a queued message
+async fn deliver(http: &serenity::Http, target: AlertTarget, payload: &serde_json::Value) -> Result<(), serenity::Error> {
+    let channel_id = match target {
+        AlertTarget::Channel(id) => serenity::ChannelId::new(id),
+        AlertTarget::User(id) => serenity::UserId::new(id).create_dm_channel(http).await?.id
+    };
+
+    http.send_message(channel_id, Vec::new(), payload).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(hour: u32, minute: u32) -> chrono::DateTime<chrono::Utc> {
+        chrono::Utc.with_ymd_and_hms(2026, 10, 19, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn no_quiet_hours_unless_both_are_set() {
+        assert!(!in_quiet_hours(at(3, 0), 0, None, None));
+        assert!(!in_quiet_hours(at(3, 0), 0, Some(1), None));
+        assert!(!in_quiet_hours(at(3, 0), 0, None, Some(6)));
+    }
+
+    #[test]
+    fn equal_start_and_end_is_off() {
+        assert!(!in_quiet_hours(at(5, 0), 0, Some(5), Some(5)));
+    }
+
+    #[test]
+    fn quiet_hours_within_a_day() {
+        assert!(!in_quiet_hours(at(0, 59), 0, Some(1), Some(6)));
+        assert!(in_quiet_hours(at(1, 0), 0, Some(1), Some(6)));
+        assert!(in_quiet_hours(at(5, 59), 0, Some(1), Some(6)));
+        assert!(!in_quiet_hours(at(6, 0), 0, Some(1), Some(6)));
+    }
+
+    #[test]
+    fn quiet_hours_wrap_past_midnight() {
+        assert!(!in_quiet_hours(at(21, 59), 0, Some(22), Some(7)));
+        assert!(in_quiet_hours(at(22, 0), 0, Some(22), Some(7)));
+        assert!(in_quiet_hours(at(0, 0), 0, Some(22), Some(7)));
+        assert!(in_quiet_hours(at(6, 59), 0, Some(22), Some(7)));
+        assert!(!in_quiet_hours(at(7, 0), 0, Some(22), Some(7)));
+    }
+
+    #[test]
+    fn positive_offset_moves_into_the_next_day() {
+        // 20:30 UTC is 00:00 the next day at UTC+3:30
+        assert!(in_quiet_hours(at(20, 30), 210, Some(0), Some(6)));
+        assert!(!in_quiet_hours(at(20, 29), 210, Some(0), Some(6)));
+    }
+
+    #[test]
+    fn negative_offset_moves_into_the_previous_day() {
+        // 02:00 UTC is 21:00 the day before at UTC-5
+        assert!(in_quiet_hours(at(2, 0), -300, Some(21), Some(8)));
+        assert!(!in_quiet_hours(at(1, 59), -300, Some(21), Some(8)));
+        assert!(!in_quiet_hours(at(13, 0), -300, Some(21), Some(8)));
+    }
+
+    fn alert(target: AlertTarget, key: &str) -> Notification {
+        Notification {
+            target,
+            guild_id: None,
+            key: key.to_string(),
+            summary: String::from("Something happened"),
+            message: serenity::CreateMessage::new().content("Something happened"),
+        }
+    }
+
+    async fn queued(database: &sqlx::MySqlPool) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM notification_queue")
+            .fetch_one(database)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn repeated_events_are_only_queued_once(database: sqlx::MySqlPool) {
+        assert!(notify(&database, alert(AlertTarget::Channel(1), "server:a")).await);
+        assert!(notify(&database, alert(AlertTarget::Channel(1), "server:a")).await);
+        assert_eq!(queued(&database).await, 1);
+
+        // Other events and other targets aren't duplicates
+        assert!(notify(&database, alert(AlertTarget::Channel(1), "server:b")).await);
+        assert!(notify(&database, alert(AlertTarget::Channel(2), "server:a")).await);
+        assert!(notify(&database, alert(AlertTarget::User(1), "server:a")).await);
+        assert_eq!(queued(&database).await, 4);
+    }
+
+    #[sqlx::test]
+    async fn events_alert_again_once_their_key_expires(database: sqlx::MySqlPool) {
+        assert!(notify(&database, alert(AlertTarget::Channel(1), "server:a")).await);
+
+        sqlx::query("UPDATE notification_keys SET created_at = NOW() - INTERVAL ? MINUTE")
+            .bind(DEDUP_WINDOW + 1)
+            .execute(&database)
+            .await
+            .unwrap();
+
+        assert!(notify(&database, alert(AlertTarget::Channel(1), "server:a")).await);
+        assert!(notify(&database, alert(AlertTarget::Channel(1), "server:a")).await);
+        assert_eq!(queued(&database).await, 2);
+    }
+}
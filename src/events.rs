@@ -0,0 +1,282 @@
+use crate::data::DetailedServer;
+use std::collections::HashMap;
+
+// Changes between two polls of the same server
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ServerEvent {
+    CameOnline,
+    WentOffline,
+    // The previous snapshot holds the final scoreboard of the ended match
+    MatchEnded { map: String, mode: String },
+    ModeChanged { from: String, to: String },
+    MapChanged { from: String, to: String },
+    MastermodeChanged { from: String, to: String },
+    PlayerLeft { name: String },
+    PlayerJoined { name: String },
+}
+
+// Compare two snapshots of a server, where None means it wasn't listed
+pub fn diff(old: Option<&DetailedServer>, new: Option<&DetailedServer>) -> Vec<ServerEvent> {
+    let (old, new) = match (old, new) {
+        (Some(old), Some(new)) => (old, new),
+        (None, Some(_)) => return vec![ServerEvent::CameOnline],
+        (Some(_), None) => return vec![ServerEvent::WentOffline],
+        (None, None) => return Vec::new(),
+    };
+
+    let mut events = Vec::new();
+
+    if match_ended(old, new) {
+        events.push(ServerEvent::MatchEnded {
+            map: old.mapName.clone(),
+            mode: old.gameMode.clone(),
+        });
+    }
+
+    if old.gameMode != new.gameMode {
+        events.push(ServerEvent::ModeChanged { from: old.gameMode.clone(), to: new.gameMode.clone() });
+    }
+    if old.mapName != new.mapName {
+        events.push(ServerEvent::MapChanged { from: old.mapName.clone(), to: new.mapName.clone() });
+    }
+    if old.masterMode != new.masterMode {
+        events.push(ServerEvent::MastermodeChanged { from: old.masterMode.clone(), to: new.masterMode.clone() });
+    }
+
+    // Count names so duplicate names are tracked separately
+    let mut counts: HashMap<&str, i64> = HashMap::new();
+    for player in &new.players {
+        *counts.entry(player.name.as_str()).or_insert(0) += 1;
+    }
+    for player in &old.players {
+        *counts.entry(player.name.as_str()).or_insert(0) -= 1;
+    }
+
+    let mut changes: Vec<(&str, i64)> = counts.into_iter().filter(|(_, count)| *count != 0).collect();
+    changes.sort();
+
+    for (name, count) in &changes {
+        for _ in 0..(-count).max(0) {
+            events.push(ServerEvent::PlayerLeft { name: name.to_string() });
+        }
+    }
+    for (name, count) in &changes {
+        for _ in 0..(*count).max(0) {
+            events.push(ServerEvent::PlayerJoined { name: name.to_string() });
+        }
+    }
+
+    events
+}
+
+// A timed match ends when it reaches intermission, or when a new one starts without
+// intermission being seen. Matches already in intermission were reported when it began.
+fn match_ended(old: &DetailedServer, new: &DetailedServer) -> bool {
+    if old.gameMode == "coop_edit" || old.timeLeft <= 0 {
+        return false;
+    }
+
+    let intermission = new.timeLeft <= 0;
+    let new_game = old.mapName != new.mapName || old.gameMode != new.gameMode;
+    let rollover = new.timeLeft > old.timeLeft;
+
+    intermission || new_game || rollover
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::ServerPlayer;
+
+    fn server(map: &str, mode: &str, time_left: i64, players: &[&str]) -> DetailedServer {
+        DetailedServer {
+            mapName: map.to_string(),
+            gameMode: mode.to_string(),
+            masterMode: String::from("open"),
+            timeLeft: time_left,
+            players: players.iter()
+                .map(|name| ServerPlayer { name: name.to_string(), ..Default::default() })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn match_ended(map: &str, mode: &str) -> ServerEvent {
+        ServerEvent::MatchEnded { map: map.to_string(), mode: mode.to_string() }
+    }
+
+    fn joined(name: &str) -> ServerEvent {
+        ServerEvent::PlayerJoined { name: name.to_string() }
+    }
+
+    fn left(name: &str) -> ServerEvent {
+        ServerEvent::PlayerLeft { name: name.to_string() }
+    }
+
+    #[test]
+    fn offline_both_times_is_quiet() {
+        assert_eq!(diff(None, None), Vec::new());
+    }
+
+    #[test]
+    fn coming_online_only_reports_online() {
+        let new = server("complex", "ffa", 300, &["a", "b"]);
+        assert_eq!(diff(None, Some(&new)), vec![ServerEvent::CameOnline]);
+    }
+
+    #[test]
+    fn going_offline_mid_match_only_reports_offline() {
+        let old = server("complex", "ffa", 300, &["a"]);
+        assert_eq!(diff(Some(&old), None), vec![ServerEvent::WentOffline]);
+    }
+
+    #[test]
+    fn unchanged_server_is_quiet() {
+        let old = server("complex", "ffa", 300, &["a", "b"]);
+        let new = server("complex", "ffa", 270, &["b", "a"]);
+        assert_eq!(diff(Some(&old), Some(&new)), Vec::new());
+    }
+
+    #[test]
+    fn paused_match_is_quiet() {
+        let old = server("complex", "ffa", 300, &["a"]);
+        let new = server("complex", "ffa", 300, &["a"]);
+        assert_eq!(diff(Some(&old), Some(&new)), Vec::new());
+    }
+
+    #[test]
+    fn players_joining_and_leaving() {
+        let old = server("complex", "ffa", 300, &["a", "b"]);
+        let new = server("complex", "ffa", 270, &["b", "c", "d"]);
+        assert_eq!(diff(Some(&old), Some(&new)), vec![left("a"), joined("c"), joined("d")]);
+    }
+
+    #[test]
+    fn duplicate_names_are_counted() {
+        let old = server("complex", "ffa", 300, &["unnamed"]);
+        let new = server("complex", "ffa", 300, &["unnamed", "unnamed", "unnamed"]);
+        assert_eq!(diff(Some(&old), Some(&new)), vec![joined("unnamed"), joined("unnamed")]);
+
+        assert_eq!(diff(Some(&new), Some(&old)), vec![left("unnamed"), left("unnamed")]);
+    }
+
+    #[test]
+    fn mastermode_change() {
+        let old = server("complex", "ffa", 300, &[]);
+        let mut new = server("complex", "ffa", 270, &[]);
+        new.masterMode = String::from("locked");
+
+        assert_eq!(diff(Some(&old), Some(&new)), vec![ServerEvent::MastermodeChanged {
+            from: String::from("open"),
+            to: String::from("locked"),
+        }]);
+    }
+
+    #[test]
+    fn entering_intermission_ends_the_match() {
+        let old = server("reissen", "insta_ctf", 20, &["a"]);
+        let new = server("reissen", "insta_ctf", 0, &["a"]);
+        assert_eq!(diff(Some(&old), Some(&new)), vec![match_ended("reissen", "insta_ctf")]);
+    }
+
+    #[test]
+    fn negative_time_counts_as_intermission() {
+        let old = server("reissen", "insta_ctf", 20, &[]);
+        let new = server("reissen", "insta_ctf", -1, &[]);
+        assert_eq!(diff(Some(&old), Some(&new)), vec![match_ended("reissen", "insta_ctf")]);
+    }
+
+    #[test]
+    fn leaving_intermission_does_not_end_the_match_again() {
+        let old = server("reissen", "insta_ctf", 0, &[]);
+        let new = server("forge", "insta_ctf", 600, &[]);
+        assert_eq!(diff(Some(&old), Some(&new)), vec![ServerEvent::MapChanged {
+            from: String::from("reissen"),
+            to: String::from("forge"),
+        }]);
+    }
+
+    #[test]
+    fn staying_in_intermission_is_quiet() {
+        let old = server("reissen", "insta_ctf", 0, &[]);
+        let new = server("reissen", "insta_ctf", 0, &[]);
+        assert_eq!(diff(Some(&old), Some(&new)), Vec::new());
+    }
+
+    #[test]
+    fn missed_intermission_with_new_map_ends_the_match() {
+        let old = server("reissen", "insta_ctf", 15, &[]);
+        let new = server("forge", "insta_ctf", 590, &[]);
+        assert_eq!(diff(Some(&old), Some(&new)), vec![
+            match_ended("reissen", "insta_ctf"),
+            ServerEvent::MapChanged { from: String::from("reissen"), to: String::from("forge") },
+        ]);
+    }
+
+    #[test]
+    fn missed_intermission_on_same_map_ends_the_match() {
+        let old = server("reissen", "insta_ctf", 15, &[]);
+        let new = server("reissen", "insta_ctf", 590, &[]);
+        assert_eq!(diff(Some(&old), Some(&new)), vec![match_ended("reissen", "insta_ctf")]);
+    }
+
+    #[test]
+    fn mode_change_mid_match_ends_the_match() {
+        let old = server("reissen", "insta_ctf", 300, &[]);
+        let new = server("reissen", "efficiency", 600, &[]);
+        assert_eq!(diff(Some(&old), Some(&new)), vec![
+            match_ended("reissen", "insta_ctf"),
+            ServerEvent::ModeChanged { from: String::from("insta_ctf"), to: String::from("efficiency") },
+        ]);
+    }
+
+    #[test]
+    fn map_and_mode_change_report_both() {
+        let old = server("reissen", "insta_ctf", 300, &[]);
+        let new = server("complex", "ffa", 600, &[]);
+        assert_eq!(diff(Some(&old), Some(&new)), vec![
+            match_ended("reissen", "insta_ctf"),
+            ServerEvent::ModeChanged { from: String::from("insta_ctf"), to: String::from("ffa") },
+            ServerEvent::MapChanged { from: String::from("reissen"), to: String::from("complex") },
+        ]);
+    }
+
+    #[test]
+    fn coop_edit_never_ends_a_match() {
+        let old = server("firstevermap", "coop_edit", 0, &[]);
+        let new = server("secondevermap", "coop_edit", 0, &[]);
+        assert_eq!(diff(Some(&old), Some(&new)), vec![ServerEvent::MapChanged {
+            from: String::from("firstevermap"),
+            to: String::from("secondevermap"),
+        }]);
+
+        let old = server("firstevermap", "coop_edit", 100, &[]);
+        let new = server("firstevermap", "coop_edit", 200, &[]);
+        assert_eq!(diff(Some(&old), Some(&new)), Vec::new());
+    }
+
+    #[test]
+    fn switching_out_of_coop_edit_does_not_end_a_match() {
+        let old = server("complex", "coop_edit", 0, &[]);
+        let new = server("complex", "ffa", 600, &[]);
+        assert_eq!(diff(Some(&old), Some(&new)), vec![ServerEvent::ModeChanged {
+            from: String::from("coop_edit"),
+            to: String::from("ffa"),
+        }]);
+    }
+
+    #[test]
+    fn events_are_ordered() {
+        let mut old = server("reissen", "insta_ctf", 20, &["a", "b"]);
+        old.masterMode = String::from("veto");
+        let new = server("forge", "insta_ctf", 600, &["b", "c"]);
+
+        assert_eq!(diff(Some(&old), Some(&new)), vec![
+            match_ended("reissen", "insta_ctf"),
+            ServerEvent::MapChanged { from: String::from("reissen"), to: String::from("forge") },
+            ServerEvent::MastermodeChanged { from: String::from("veto"), to: String::from("open") },
+            left("a"),
+            joined("c"),
+        ]);
+    }
+}
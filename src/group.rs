@@ -0,0 +1,231 @@
+use crate::{Context, Error};
+use crate::data::{fuzzy_rank, BookmarkGroup, ServerBookmark};
+use crate::admin::info_role;
+use crate::bookmark::{autocomplete_bookmark, name_taken};
+use poise::serenity_prelude as serenity;
+
+/// Manage named groups of server bookmarks
+#[poise::command(
+    slash_command,
+    subcommands("create", "delete", "add", "remove", "list"),
+    subcommand_required,
+    guild_only
+)]
+pub async fn bkgroup(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Create a bookmark group
+#[poise::command(
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS",
+    guild_only
+)]
+pub async fn create(
+    ctx: Context<'_>,
+    #[description = "Name for the group"] group: String,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+
+    // Groups share names with bookmarks so /bk can show either
+    if name_taken(&ctx.data().database, guild_id, &group).await {
+        return Err("That name is already used!".into());
+    }
+
+    sqlx::query!("INSERT INTO bookmark_groups (guild_id, group_name) VALUES (?, ?)", guild_id, group)
+        .execute(&ctx.data().database)
+        .await
+        .unwrap();
+
+    let msg = format!("{}, created a bookmark group named {}!", ctx.author(), group);
+    ctx.say(msg).await?;
+
+    Ok(())
+}
+
+/// Delete a bookmark group, keeping its bookmarks
+#[poise::command(
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS",
+    guild_only
+)]
+pub async fn delete(
+    ctx: Context<'_>,
+    #[description = "Name of the group"]
+    #[autocomplete = "autocomplete_group"] group: String,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+
+    let group_info = match find_group(&ctx.data().database, guild_id, &group).await {
+        Some(info) => info,
+        None => return Err("No group with that name exists!".into())
+    };
+
+    sqlx::query!("DELETE FROM bookmark_groups WHERE id = ?", group_info.id)
+        .execute(&ctx.data().database)
+        .await
+        .unwrap();
+
+    let msg = format!("{}, deleted the bookmark group {}!", ctx.author(), group);
+    ctx.say(msg).await?;
+
+    Ok(())
+}
+
+/// Add a bookmark to a group
+#[poise::command(
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS",
+    guild_only
+)]
+pub async fn add(
+    ctx: Context<'_>,
+    #[description = "Name of the group"]
+    #[autocomplete = "autocomplete_group"] group: String,
+    #[description = "Name of the server bookmark"]
+    #[autocomplete = "autocomplete_bookmark"] bookmark: String,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+
+    let group_info = match find_group(&ctx.data().database, guild_id, &group).await {
+        Some(info) => info,
+        None => return Err("No group with that name exists!".into())
+    };
+
+    let bookmark_info = match sqlx::query_as!(ServerBookmark, "SELECT * FROM server_bookmarks WHERE guild_id = ? AND bookmark_name = ?", guild_id, bookmark)
+        .fetch_optional(&ctx.data().database)
+        .await
+        .unwrap()
+    {
+        Some(info) => info,
+        None => return Err("No bookmark with that name exists!".into())
+    };
+
+    let count = sqlx::query!("SELECT COUNT(bookmark_id) AS count FROM bookmark_group_members WHERE group_id = ? AND bookmark_id = ?", group_info.id, bookmark_info.id)
+        .fetch_one(&ctx.data().database)
+        .await
+        .unwrap();
+
+    if count.count > 0 {
+        return Err("That bookmark is already in the group!".into());
+    }
+
+    sqlx::query!("INSERT INTO bookmark_group_members (group_id, bookmark_id) VALUES (?, ?)", group_info.id, bookmark_info.id)
+        .execute(&ctx.data().database)
+        .await
+        .unwrap();
+
+    let msg = format!("{}, added {} to the bookmark group {}!", ctx.author(), bookmark, group);
+    ctx.say(msg).await?;
+
+    Ok(())
+}
+
+/// Remove a bookmark from a group
+#[poise::command(
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS",
+    guild_only
+)]
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "Name of the group"]
+    #[autocomplete = "autocomplete_group"] group: String,
+    #[description = "Name of the server bookmark"]
+    #[autocomplete = "autocomplete_bookmark"] bookmark: String,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+
+    let group_info = match find_group(&ctx.data().database, guild_id, &group).await {
+        Some(info) => info,
+        None => return Err("No group with that name exists!".into())
+    };
+
+    let removed = sqlx::query!("DELETE bookmark_group_members FROM bookmark_group_members JOIN server_bookmarks ON server_bookmarks.id = bookmark_group_members.bookmark_id WHERE bookmark_group_members.group_id = ? AND server_bookmarks.bookmark_name = ?", group_info.id, bookmark)
+        .execute(&ctx.data().database)
+        .await
+        .unwrap();
+
+    if removed.rows_affected() == 0 {
+        return Err("That bookmark isn't in the group!".into());
+    }
+
+    let msg = format!("{}, removed {} from the bookmark group {}!", ctx.author(), bookmark, group);
+    ctx.say(msg).await?;
+
+    Ok(())
+}
+
+/// List bookmark groups and their bookmarks
+#[poise::command(
+    slash_command,
+    check = "info_role",
+    guild_only
+)]
+pub async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+
+    let groups: Vec<BookmarkGroup> = sqlx::query_as!(BookmarkGroup, "SELECT * FROM bookmark_groups WHERE guild_id = ?", guild_id)
+        .fetch_all(&ctx.data().database)
+        .await
+        .unwrap();
+
+    if groups.is_empty() {
+        return Err("No bookmark groups saved!".into());
+    }
+
+    let mut group_list = String::new();
+    for group in &groups {
+        let members: Vec<String> = group_bookmarks(&ctx.data().database, group.id).await
+            .into_iter()
+            .map(|bookmark| bookmark.bookmark_name)
+            .collect();
+
+        group_list = format!("{group_list}- **{}** ({}): {}\n",
+            group.group_name,
+            members.len(),
+            if members.is_empty() { String::from("*empty*") } else { members.join(", ") }
+        );
+    }
+
+    ctx.send(poise::CreateReply::default().embed(serenity::CreateEmbed::new()
+        .title("Bookmark Groups")
+        .description(group_list)
+    )).await?;
+
+    Ok(())
+}
+
+// Look up a group by name
+pub async fn find_group(database: &sqlx::MySqlPool, guild_id: u64, group: &str) -> Option<BookmarkGroup> {
+    sqlx::query_as!(BookmarkGroup, "SELECT * FROM bookmark_groups WHERE guild_id = ? AND group_name = ?", guild_id, group)
+        .fetch_optional(database)
+        .await
+        .unwrap()
+}
+
+// Bookmarks assigned to a group
+pub async fn group_bookmarks(database: &sqlx::MySqlPool, group_id: i32) -> Vec<ServerBookmark> {
+    sqlx::query_as!(ServerBookmark, "SELECT server_bookmarks.* FROM server_bookmarks JOIN bookmark_group_members ON bookmark_group_members.bookmark_id = server_bookmarks.id WHERE bookmark_group_members.group_id = ?", group_id)
+        .fetch_all(database)
+        .await
+        .unwrap()
+}
+
+// Suggest the guild's bookmark groups
+pub async fn autocomplete_group(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let guild_id = match ctx.guild_id() {
+        Some(id) => id.get(),
+        None => return Vec::new()
+    };
+
+    let groups: Vec<String> = sqlx::query!("SELECT group_name FROM bookmark_groups WHERE guild_id = ?", guild_id)
+        .fetch_all(&ctx.data().database)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|group| group.group_name)
+        .collect();
+
+    fuzzy_rank(partial, groups, |group| group.as_str())
+}
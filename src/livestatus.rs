@@ -0,0 +1,334 @@
+use crate::{Context, Error};
+use crate::data::{escape_markdown, fuzzy_rank, LiveStatus, ServerBookmark};
+use crate::poller::{next_snapshot, Snapshot, SnapshotBus};
+use crate::server::server_summary;
+use poise::serenity_prelude as serenity;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// Seconds between updates when none is given
+const DEFAULT_UPDATE_INTERVAL: u32 = 60;
+
+// Embeds hold at most 25 fields
+const MAX_LIVE_BOOKMARKS: usize = 25;
+
+// Characters shared by all fields, leaving room under Discord's 6000 character limit
+const FIELD_TEXT_BUDGET: usize = 4500;
+
+// Updates are due on the first snapshot after the interval, give or take a little
+const UPDATE_SLACK: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Manage the self-updating server status message
+#[poise::command(
+    slash_command,
+    subcommands("set", "disable"),
+    subcommand_required,
+    guild_only
+)]
+pub async fn livestatus(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Post a message that keeps showing the status of bookmarked servers
+#[poise::command(
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS",
+    guild_only
+)]
+pub async fn set(
+    ctx: Context<'_>,
+    #[description = "Channel to post the status message in"]
+    #[channel_types("Text", "News")] channel: serenity::GuildChannel,
+    #[description = "Bookmarks to show, separated by commas. Leave empty for all bookmarks."]
+    #[autocomplete = "autocomplete_bookmark_list"] bookmarks: Option<String>,
+    #[description = "Seconds between updates"]
+    #[min = 30]
+    #[max = 3600] interval: Option<u32>,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    let guild_id = ctx.guild_id().unwrap().get();
+    let update_interval = interval.unwrap_or(DEFAULT_UPDATE_INTERVAL);
+
+    let server_bookmarks: Vec<ServerBookmark> = sqlx::query_as!(ServerBookmark, "SELECT * FROM server_bookmarks WHERE guild_id = ? ORDER BY bookmark_name", guild_id)
+        .fetch_all(&ctx.data().database)
+        .await
+        .unwrap();
+
+    // Resolve the chosen bookmarks in the order given
+    let mut chosen: Vec<ServerBookmark> = Vec::new();
+    for name in bookmarks.iter().flat_map(|names| names.split(',')).map(str::trim).filter(|name| !name.is_empty()) {
+        let bookmark = match server_bookmarks.iter().find(|bookmark| bookmark.bookmark_name.eq_ignore_ascii_case(name)) {
+            Some(bookmark) => bookmark,
+            None => return Err(format!("No bookmark named {} exists!", name).into())
+        };
+
+        if !chosen.iter().any(|added| added.id == bookmark.id) {
+            chosen.push(bookmark.clone());
+        }
+    }
+
+    if chosen.len() > MAX_LIVE_BOOKMARKS {
+        return Err(format!("A status message can show at most {} bookmarks!", MAX_LIVE_BOOKMARKS).into());
+    }
+
+    let all_bookmarks = chosen.is_empty();
+    let shown = if all_bookmarks { server_bookmarks } else { chosen };
+    if shown.is_empty() {
+        return Err("There are no bookmarks to show! Add some with /bkadd first.".into());
+    }
+
+    let embed = build_live_embed(ctx.data().snapshots.latest().as_deref(), &shown, update_interval);
+    let message = match channel.id.send_message(ctx, serenity::CreateMessage::new().embed(embed)).await {
+        Ok(message) => message,
+        Err(_) => return Err(format!("I can't post messages in {}!", channel).into())
+    };
+
+    let previous = find_live_status(&ctx.data().database, guild_id).await;
+
+    let status = LiveStatus {
+        guild_id,
+        channel_id: channel.id.get(),
+        message_id: message.id.get(),
+        update_interval,
+        all_bookmarks,
+    };
+    let shown_ids: Vec<i32> = if all_bookmarks { Vec::new() } else { shown.iter().map(|bookmark| bookmark.id).collect() };
+
+    // Don't leave behind a message nothing will update
+    if let Err(e) = save_live_status(&ctx.data().database, &status, &shown_ids).await {
+        let _ = message.delete(ctx).await;
+        return Err(e.into());
+    }
+
+    // Replace any previous status message
+    if let Some(previous) = previous {
+        let _ = serenity::ChannelId::new(previous.channel_id).delete_message(ctx, previous.message_id).await;
+    }
+
+    let msg = format!("{}, the server status in {} now updates every {} seconds!", ctx.author(), channel, update_interval);
+    ctx.say(msg).await?;
+
+    Ok(())
+}
+
+/// Stop updating the server status message and delete it
+#[poise::command(
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS",
+    guild_only
+)]
+pub async fn disable(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+
+    let status = match find_live_status(&ctx.data().database, guild_id).await {
+        Some(status) => status,
+        None => return Err("There is no server status message to disable!".into())
+    };
+
+    sqlx::query!("DELETE FROM live_status WHERE guild_id = ?", guild_id)
+        .execute(&ctx.data().database)
+        .await
+        .unwrap();
+
+    // The message may already be gone
+    let _ = serenity::ChannelId::new(status.channel_id).delete_message(ctx, status.message_id).await;
+
+    let msg = format!("{}, the server status message has been disabled!", ctx.author());
+    ctx.say(msg).await?;
+
+    Ok(())
+}
+
+// Edit every status message that is due on each new snapshot
+pub async fn run(http: Arc<serenity::Http>, database: sqlx::MySqlPool, snapshots: Arc<SnapshotBus>) {
+    let mut receiver = snapshots.subscribe();
+    let mut last_update: HashMap<u64, std::time::Instant> = HashMap::new();
+
+    while let Some(snapshot) = next_snapshot(&mut receiver).await {
+        let statuses: Vec<LiveStatus> = sqlx::query_as!(LiveStatus, "SELECT guild_id, channel_id, message_id, update_interval, all_bookmarks AS `all_bookmarks: bool` FROM live_status")
+            .fetch_all(&database)
+            .await
+            .unwrap_or_default();
+
+        for status in statuses {
+            let interval = std::time::Duration::from_secs(status.update_interval as u64);
+            if last_update.get(&status.guild_id).is_some_and(|updated_at| updated_at.elapsed() + UPDATE_SLACK < interval) {
+                continue;
+            }
+            last_update.insert(status.guild_id, std::time::Instant::now());
+
+            let embed = build_live_embed(Some(&snapshot), &live_bookmarks(&database, &status).await, status.update_interval);
+            update_message(&http, &database, &status, embed).await;
+        }
+    }
+}
+
+// Edit the status message, posting it again if it was deleted and disabling it
+// if the channel is gone or can't be posted in
+async fn update_message(http: &Arc<serenity::Http>, database: &sqlx::MySqlPool, status: &LiveStatus, embed: serenity::CreateEmbed) {
+    let channel = serenity::ChannelId::new(status.channel_id);
+
+    let edited = channel.edit_message(http, status.message_id, serenity::EditMessage::new().embed(embed.clone())).await;
+    let error = match edited {
+        Ok(_) => return,
+        Err(e) => e
+    };
+
+    if http_status(&error) == Some(404) {
+        match channel.send_message(http, serenity::CreateMessage::new().embed(embed)).await {
+            Ok(message) => {
+                sqlx::query!("UPDATE live_status SET message_id = ? WHERE guild_id = ?", message.id.get(), status.guild_id)
+                    .execute(database)
+                    .await
+                    .unwrap();
+                return;
+            }
+            Err(e) if matches!(http_status(&e), Some(403 | 404)) => {}
+            Err(e) => {
+                println!("[ ERROR ] Reposting live status for guild {} failed: {e}", status.guild_id);
+                return;
+            }
+        }
+    } else if http_status(&error) != Some(403) {
+        println!("[ ERROR ] Updating live status for guild {} failed: {error}", status.guild_id);
+        return;
+    }
+
+    println!("[ INFO ] Disabling live status for guild {}, its channel is unavailable", status.guild_id);
+    sqlx::query!("DELETE FROM live_status WHERE guild_id = ?", status.guild_id)
+        .execute(database)
+        .await
+        .unwrap();
+}
+
+pub fn http_status(error: &serenity::Error) -> Option<u16> {
+    match error {
+        serenity::Error::Http(e) => e.status_code().map(|code| code.as_u16()),
+        _ => None
+    }
+}
+
+// Replace the guild's live status and its bookmarks in one transaction
+async fn save_live_status(database: &sqlx::MySqlPool, status: &LiveStatus, bookmark_ids: &[i32]) -> Result<(), sqlx::Error> {
+    let mut tx = database.begin().await?;
+
+    sqlx::query!("DELETE FROM live_status WHERE guild_id = ?", status.guild_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query!("INSERT INTO live_status (guild_id, channel_id, message_id, update_interval, all_bookmarks) VALUES (?, ?, ?, ?, ?)",
+        status.guild_id, status.channel_id, status.message_id, status.update_interval, status.all_bookmarks)
+        .execute(&mut *tx)
+        .await?;
+
+    for bookmark_id in bookmark_ids {
+        sqlx::query!("INSERT INTO live_status_bookmarks (guild_id, bookmark_id) VALUES (?, ?)", status.guild_id, bookmark_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await
+}
+
+async fn find_live_status(database: &sqlx::MySqlPool, guild_id: u64) -> Option<LiveStatus> {
+    sqlx::query_as!(LiveStatus, "SELECT guild_id, channel_id, message_id, update_interval, all_bookmarks AS `all_bookmarks: bool` FROM live_status WHERE guild_id = ?", guild_id)
+        .fetch_optional(database)
+        .await
+        .unwrap()
+}
+
+// Bookmarks shown on a status message
+async fn live_bookmarks(database: &sqlx::MySqlPool, status: &LiveStatus) -> Vec<ServerBookmark> {
+    if status.all_bookmarks {
+        sqlx::query_as!(ServerBookmark, "SELECT * FROM server_bookmarks WHERE guild_id = ? ORDER BY bookmark_name", status.guild_id)
+            .fetch_all(database)
+            .await
+            .unwrap_or_default()
+    } else {
+        sqlx::query_as!(ServerBookmark, "SELECT server_bookmarks.* FROM server_bookmarks JOIN live_status_bookmarks ON live_status_bookmarks.bookmark_id = server_bookmarks.id WHERE live_status_bookmarks.guild_id = ? ORDER BY bookmark_name", status.guild_id)
+            .fetch_all(database)
+            .await
+            .unwrap_or_default()
+    }
+}
+
+// One field per bookmark, using the same summary as /server
+fn build_live_embed(snapshot: Option<&Snapshot>, server_bookmarks: &[ServerBookmark], update_interval: u32) -> serenity::CreateEmbed {
+    let mut embed = serenity::CreateEmbed::new()
+        .colour(0xFF0000)
+        .title("Server status")
+        .timestamp(serenity::Timestamp::now());
+
+    if server_bookmarks.is_empty() {
+        embed = embed.description("*No bookmarks to show.*");
+    }
+
+    let shown = server_bookmarks.len().min(MAX_LIVE_BOOKMARKS);
+    let field_budget = (FIELD_TEXT_BUDGET / shown.max(1)).min(1024);
+
+    let mut online_players = 0;
+    for bookmark in &server_bookmarks[..shown] {
+        let name = format!("{}{}",
+            bookmark.emoji.as_ref().map(|emoji| format!("{emoji} ")).unwrap_or_default(),
+            bookmark.bookmark_name
+        );
+
+        let value = match snapshot.map(|snapshot| snapshot.detailed_server(&bookmark.host, bookmark.port)) {
+            Some(Some(server)) => {
+                online_players += server.clients;
+                let summary = format!("{}\n`/connect {} {}`", server_summary(server), bookmark.host, bookmark.port);
+
+                // Fill what is left of the field with players
+                let players = server.all_active_players.clone().unwrap_or_default()
+                    .into_iter()
+                    .map(escape_markdown)
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                let room = field_budget.saturating_sub(summary.chars().count() + 2);
+                if players.is_empty() || room < 4 {
+                    summary
+                } else if players.chars().count() > room {
+                    format!("{}\n{}…", summary, players.chars().take(room - 1).collect::<String>())
+                } else {
+                    format!("{}\n{}", summary, players)
+                }
+            }
+            Some(None) => String::from("*Offline*"),
+            None => String::from("*Waiting for the tracker...*")
+        };
+
+        embed = embed.field(name, value, false);
+    }
+
+    let mut footer = format!("{} players online | Updates every {} seconds", online_players, update_interval);
+    if server_bookmarks.len() > shown {
+        footer = format!("{} | {} more bookmarks not shown", footer, server_bookmarks.len() - shown);
+    }
+
+    embed.footer(serenity::CreateEmbedFooter::new(footer))
+}
+
+// Complete the last bookmark in a comma separated list
+async fn autocomplete_bookmark_list(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let guild_id = match ctx.guild_id() {
+        Some(id) => id.get(),
+        None => return Vec::new()
+    };
+
+    let (chosen, last) = match partial.rfind(',') {
+        Some(i) => (&partial[..=i], partial[i + 1..].trim_start()),
+        None => ("", partial)
+    };
+
+    let server_bookmarks: Vec<ServerBookmark> = sqlx::query_as!(ServerBookmark, "SELECT * FROM server_bookmarks WHERE guild_id = ?", guild_id)
+        .fetch_all(&ctx.data().database)
+        .await
+        .unwrap_or_default();
+
+    fuzzy_rank(last, server_bookmarks, |bookmark| &bookmark.bookmark_name)
+        .into_iter()
+        .map(|bookmark| format!("{}{}{}", chosen, if chosen.is_empty() { "" } else { " " }, bookmark.bookmark_name))
+        // Choices can't be longer than 100 characters
+        .filter(|choice| choice.chars().count() <= 100)
+        .collect()
+}
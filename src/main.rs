@@ -9,11 +9,26 @@ mod server;
 mod clan;
 mod admin;
 mod bookmark;
+mod backup;
+mod group;
+mod personal;
+mod poller;
+mod events;
+mod livestatus;
+mod alerts;
+mod dispatch;
+mod watch;
+mod summary;
+mod subscription;
+mod uptime;
 
 pub struct Data {
     // User data, which is stored and accessible in all command invocations
     database: sqlx::MySqlPool,
     client: reqwest::Client,
+    server_list: tokio::sync::Mutex<Option<(std::time::Instant, Vec<data::BasicServer>)>>,
+    autocomplete_cache: tokio::sync::Mutex<std::collections::HashMap<String, (std::time::Instant, Vec<String>)>>,
+    snapshots: std::sync::Arc<poller::SnapshotBus>,
 }
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
@@ -74,22 +89,41 @@ async fn main() {
         .build()
         .unwrap();
 
+    let snapshots = std::sync::Arc::new(poller::SnapshotBus::new());
+
     let framework = poise::Framework::builder()
         .setup(move |ctx, _ready, framework| {
             Box::pin(async move {
                 poise::builtins::register_globally(ctx, &framework.options().commands).await?;
+
+                // Background tasks
+                tokio::spawn(poller::run(snapshots.clone(), database.clone(), client.clone()));
+                tokio::spawn(bookmark::health_check(database.clone(), snapshots.clone()));
+                tokio::spawn(livestatus::run(ctx.http.clone(), database.clone(), snapshots.clone()));
+                tokio::spawn(uptime::run(database.clone(), snapshots.clone()));
+                tokio::spawn(alerts::run(database.clone(), client.clone(), snapshots.clone()));
+                tokio::spawn(dispatch::run(ctx.http.clone(), database.clone()));
+
                 Ok(Data {
                     database: database,
                     client,
+                    server_list: Default::default(),
+                    autocomplete_cache: Default::default(),
+                    snapshots,
                 })
             })
         })
         .options(poise::FrameworkOptions {
             commands: vec![
                 admin::setrole(),
+                admin::setalertchannel(),
+                admin::settimezone(),
+                admin::setquiethours(),
+                admin::setdigest(),
 
                 server::server(),
                 server::listservers(),
+                server::online(),
 
                 player::findplayer(),
                 player::player(),
@@ -97,9 +131,25 @@ async fn main() {
                 clan::claninfo(),
 
                 bookmark::bk(),
+                bookmark::bkstatus(),
                 bookmark::bkadd(),
+                bookmark::bkedit(),
                 bookmark::bkdelete(),
-                bookmark::bklist()
+                bookmark::bklist(),
+                backup::bkexport(),
+                backup::bkimport(),
+                group::bkgroup(),
+                personal::mybk(),
+                livestatus::livestatus(),
+                watch::watchserver(),
+                watch::unwatchserver(),
+                watch::watchplayer(),
+                watch::unwatchplayer(),
+                watch::watchclan(),
+                watch::unwatchclan(),
+                summary::matchsummary(),
+                subscription::mapsub(),
+                uptime::uptime()
             ],
             event_handler: |ctx, event, framework, data| Box::pin(listener(ctx, event, framework, data)),
             ..Default::default()
@@ -0,0 +1,198 @@
+use crate::{Context, Error};
+use crate::data::{fuzzy_rank, option_value, paginate, parse_address, UserBookmark};
+use crate::admin::info_role;
+use crate::server::{autocomplete_host, autocomplete_roster, get_live_server_info, send_server_embed, verify_server};
+use poise::serenity_prelude as serenity;
+
+// Personal bookmarks allowed per user
+const MAX_USER_BOOKMARKS: i64 = 25;
+
+/// Manage your personal server bookmarks
+#[poise::command(
+    slash_command,
+    subcommands("add", "remove", "list", "show"),
+    subcommand_required
+)]
+pub async fn mybk(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Create a personal server bookmark
+#[poise::command(slash_command)]
+pub async fn add(
+    ctx: Context<'_>,
+    #[description = "Name for the bookmark"] bookmark: String,
+    #[description = "Server address/ip, host:port or /connect line"]
+    #[autocomplete = "autocomplete_host"] host: String,
+    #[description = "Server port (Default: 28785)"] port: Option<u32>,
+    #[description = "Add the bookmark even if the server isn't currently listed"] force: Option<bool>
+) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+    let user_id = ctx.author().id.get();
+
+    let (host, port) = parse_address(&host, port)?;
+
+    // Verify information in DB
+    let count = sqlx::query!("SELECT COUNT(id) AS count FROM user_bookmarks WHERE user_id = ?", user_id)
+        .fetch_one(&ctx.data().database)
+        .await
+        .unwrap();
+
+    if count.count >= MAX_USER_BOOKMARKS {
+        return Err(format!("You can only have {MAX_USER_BOOKMARKS} personal bookmarks!").into());
+    }
+
+    if find_user_bookmark(&ctx.data().database, user_id, &bookmark).await.is_some() {
+        return Err("That name is already used!".into());
+    }
+
+    // Check the server is live unless told otherwise
+    if !force.unwrap_or(false) {
+        if let Err(e) = verify_server(&ctx.data().client, host.clone(), port).await {
+            return Err(format!("{e} Use `force:True` to bookmark it anyway.").into());
+        }
+    }
+
+    sqlx::query!("INSERT INTO user_bookmarks (user_id, bookmark_name, host, port) VALUES (?, ?, ?, ?)", user_id, bookmark, host, port)
+        .execute(&ctx.data().database)
+        .await
+        .unwrap();
+
+    let msg = format!("{}, created a personal bookmark named {}! Address: {}:{}", ctx.author(), bookmark, host, port);
+    ctx.send(poise::CreateReply::default().content(msg).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+/// Remove a personal server bookmark
+#[poise::command(slash_command)]
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "Name of the bookmark"]
+    #[autocomplete = "autocomplete_user_bookmark"] bookmark: String
+) -> Result<(), Error> {
+    let user_id = ctx.author().id.get();
+
+    let removed = sqlx::query!("DELETE FROM user_bookmarks WHERE user_id = ? AND bookmark_name = ?", user_id, bookmark)
+        .execute(&ctx.data().database)
+        .await
+        .unwrap();
+
+    if removed.rows_affected() == 0 {
+        return Err("No personal bookmark with that name exists!".into());
+    }
+
+    let msg = format!("{}, deleted {} from your personal bookmarks!", ctx.author(), bookmark);
+    ctx.send(poise::CreateReply::default().content(msg).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+/// List your personal server bookmarks
+#[poise::command(
+    slash_command,
+    check = "info_role"
+)]
+pub async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let user_id = ctx.author().id.get();
+
+    let user_bookmarks: Vec<UserBookmark> = sqlx::query_as!(UserBookmark, "SELECT * FROM user_bookmarks WHERE user_id = ?", user_id)
+        .fetch_all(&ctx.data().database)
+        .await
+        .unwrap();
+
+    if user_bookmarks.is_empty() {
+        return Err("No personal bookmarks saved!".into());
+    }
+
+    // Build, send pages
+    let mut pages: Vec<serenity::CreateEmbed> = Vec::new();
+    for chunk in user_bookmarks.chunks(10) {
+        let mut bk_list = String::new();
+        for bookmark in chunk {
+            bk_list = format!("{bk_list}- **{0}** - `/server host:{1} port:{2}`\n",
+                bookmark.bookmark_name,
+                bookmark.host,
+                bookmark.port);
+        }
+
+        pages.push(serenity::CreateEmbed::new()
+            .title(format!("{}'s Bookmarks", ctx.author().name))
+            .description(bk_list));
+    }
+
+    paginate(ctx, pages, true).await?;
+
+    Ok(())
+}
+
+/// Grab server information from a personal bookmark
+#[poise::command(
+    slash_command,
+    check = "info_role",
+    user_cooldown = 10
+)]
+pub async fn show(
+    ctx: Context<'_>,
+    #[description = "Name of the bookmark"]
+    #[autocomplete = "autocomplete_user_bookmark"] bookmark: String,
+    #[description = "Player in game"]
+    #[autocomplete = "autocomplete_user_bookmark_player"]
+    #[max_length = 15] username: Option<String>,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    let user_id = ctx.author().id.get();
+
+    let bookmark_info = match find_user_bookmark(&ctx.data().database, user_id, &bookmark).await {
+        Some(info) => info,
+        None => return Err("No personal bookmark by that name exists!".into())
+    };
+
+    let page_url = format!("https://sauertracker.net/server/{}/{}", bookmark_info.host, bookmark_info.port);
+
+    let server_data = match get_live_server_info(ctx.data(), bookmark_info.host.clone(), bookmark_info.port).await {
+        Ok(data) => data,
+        Err(e) => return Err(e)
+    };
+
+    send_server_embed(ctx, server_data, username, page_url).await
+}
+
+// Look up a personal bookmark by name
+pub async fn find_user_bookmark(database: &sqlx::MySqlPool, user_id: u64, bookmark: &str) -> Option<UserBookmark> {
+    sqlx::query_as!(UserBookmark, "SELECT * FROM user_bookmarks WHERE user_id = ? AND bookmark_name = ?", user_id, bookmark)
+        .fetch_optional(database)
+        .await
+        .unwrap()
+}
+
+// Suggest the invoker's personal bookmarks
+pub async fn autocomplete_user_bookmark(ctx: Context<'_>, partial: &str) -> Vec<serenity::AutocompleteChoice> {
+    let user_id = ctx.author().id.get();
+
+    let user_bookmarks: Vec<UserBookmark> = sqlx::query_as!(UserBookmark, "SELECT * FROM user_bookmarks WHERE user_id = ?", user_id)
+        .fetch_all(&ctx.data().database)
+        .await
+        .unwrap_or_default();
+
+    fuzzy_rank(partial, user_bookmarks, |bookmark| &bookmark.bookmark_name)
+        .into_iter()
+        .map(|bookmark| serenity::AutocompleteChoice::new(
+            format!("{} - {}:{} (personal)", bookmark.bookmark_name, bookmark.host, bookmark.port).chars().take(100).collect::<String>(),
+            bookmark.bookmark_name,
+        ))
+        .collect()
+}
+
+// Suggest players on the server picked in the bookmark option
+async fn autocomplete_user_bookmark_player(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let bookmark_info = match option_value(ctx, "bookmark") {
+        Some(bookmark) => find_user_bookmark(&ctx.data().database, ctx.author().id.get(), &bookmark).await,
+        None => None
+    };
+
+    match bookmark_info {
+        Some(bookmark_info) => autocomplete_roster(ctx, bookmark_info.host, bookmark_info.port, partial).await,
+        None => Vec::new()
+    }
+}
@@ -1,8 +1,16 @@
 use crate::{Context, Error};
-use crate::data::{escape_markdown, grab_api_data, paginate};
+use crate::data::{cached_autocomplete, cached_narrowed, escape_markdown, fuzzy_rank, grab_api_data, paginate, AUTOCOMPLETE_TIMEOUT};
 use crate::admin::info_role;
+use crate::server::get_cached_server_list;
 use poise::serenity_prelude as serenity;
 
+// Shortest input looked up on the tracker, shorter input only suggests players online
+const FIND_MIN_LENGTH: usize = 3;
+
+// Tracker searches returning this many names might be cut short, so they're looked up again
+// rather than narrowed down for longer input
+const FIND_NARROW_LIMIT: usize = 100;
+
 /// Shows a list of similar player names up to 200 names.
 #[poise::command(
     slash_command,
@@ -12,6 +20,7 @@ use poise::serenity_prelude as serenity;
 pub async fn findplayer(
     ctx: Context<'_>,
     #[description = "Username to search for"]
+    #[autocomplete = "autocomplete_player"]
     #[max_length = 15] username: String,
 
     #[description = "Country code for user. Use __ for unknown country."]
@@ -35,6 +44,9 @@ pub async fn findplayer(
     };
 
     let data = data.as_array().unwrap();
+    if data.is_empty() {
+        return Err(format!("No players found with names similar to {username}!").into());
+    }
 
     // Format information
     let mut page_contents: Vec<String> = Vec::new();
@@ -55,15 +67,14 @@ pub async fn findplayer(
             page_contents.push(page.clone());
         }
     }
-    let page_ref: Vec<&str> = page_contents.iter().map(|x| x.as_str()).collect();
+    let pages: Vec<serenity::CreateEmbed> = page_contents.iter()
+        .map(|page| serenity::CreateEmbed::new()
+            .title(format!("Names similar to {username}"))
+            .url(page_url.clone())
+            .description(page))
+        .collect();
 
-    paginate(
-        ctx,
-        format!("Names similar to {username}"),
-        &page_ref,
-        Some(page_url),
-    )
-    .await?;
+    paginate(ctx, pages, true).await?;
 
     Ok(())
 }
@@ -77,6 +88,7 @@ pub async fn findplayer(
 pub async fn player(
     ctx: Context<'_>,
     #[description = "Username of player"]
+    #[autocomplete = "autocomplete_player"]
     #[max_length = 15] username: String,
 ) -> Result<(), Error> {
     ctx.defer().await?;
@@ -163,3 +175,41 @@ pub async fn player(
 
     Ok(())
 }
+
+// Suggest players currently online and similar names known to the tracker
+pub async fn autocomplete_player(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let mut names: Vec<String> = get_cached_server_list(ctx.data()).await
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(|server| server.players)
+        .collect();
+
+    if partial.trim().chars().count() >= FIND_MIN_LENGTH {
+        let found = match cached_narrowed(ctx.data(), "findplayer", partial, FIND_NARROW_LIMIT).await {
+            Some(found) => found,
+            None => cached_autocomplete(ctx.data(), format!("findplayer:{}", partial.to_lowercase()), find_player_names(&ctx.data().client, partial)).await
+        };
+
+        names.extend(found);
+    }
+
+    names.sort();
+    names.dedup();
+
+    fuzzy_rank(partial, names, |name| name.as_str())
+}
+
+// Names known to the tracker matching the input, given up on quickly so autocomplete answers in time
+async fn find_player_names(client: &reqwest::Client, partial: &str) -> Result<Vec<String>, Error> {
+    let api_link = reqwest::Url::parse_with_params("http://sauertracker.net/api/v2/players/find", &[("name", partial), ("country", "")])?;
+    let data: serde_json::Value = client.get(api_link)
+        .timeout(AUTOCOMPLETE_TIMEOUT)
+        .send()
+        .await?
+        .json()
+        .await?;
+
+    Ok(data.as_array()
+        .map(|players| players.iter().filter_map(|player| player["name"].as_str().map(String::from)).collect())
+        .unwrap_or_default())
+}
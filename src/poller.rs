@@ -0,0 +1,233 @@
+use crate::data::{resolve_ip, BasicServer, DetailedServer};
+use crate::events::{diff, ServerEvent};
+use crate::server::{fetch_detailed_server, get_server_list};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use tokio::sync::{broadcast, Semaphore};
+use tokio::task::JoinSet;
+
+// Time between polls of the tracker
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+// Watched servers fetched at once on each poll
+const POLL_PARALLELISM: usize = 4;
+
+// How long a bookmarked host's IP is reused before looking it up again
+const ADDRESS_TTL: std::time::Duration = std::time::Duration::from_secs(600);
+
+// Snapshots kept for subscribers that fall behind
+const SNAPSHOT_BACKLOG: usize = 16;
+
+// State of the tracker at one poll
+pub struct Snapshot {
+    pub fetched_at: std::time::Instant,
+    pub servers: Vec<BasicServer>,
+    // Detailed info for watched servers that are listed, keyed by IP and port
+    pub detailed: HashMap<(String, u32), DetailedServer>,
+    // Watched hosts resolved to the IPs the tracker lists them by
+    pub addresses: HashMap<String, String>,
+    // Changes to watched servers since the previous snapshot
+    pub events: HashMap<(String, u32), Vec<ServerEvent>>,
+    // Last full state of watched servers whose match ended since the previous snapshot
+    pub finished: HashMap<(String, u32), DetailedServer>,
+}
+
+impl Snapshot {
+    // Tracker IP for a host, resolved when it was polled
+    pub fn address(&self, host: &str) -> String {
+        self.addresses.get(host).cloned().unwrap_or(host.to_string())
+    }
+
+    pub fn server(&self, host: &str, port: u32) -> Option<&BasicServer> {
+        let host = self.address(host);
+        self.servers.iter().find(|server| server.host == host && server.port == port as i64)
+    }
+
+    pub fn detailed_server(&self, host: &str, port: u32) -> Option<&DetailedServer> {
+        self.detailed.get(&(self.address(host), port))
+    }
+
+    pub fn finished_match(&self, host: &str, port: u32) -> Option<&DetailedServer> {
+        self.finished.get(&(self.address(host), port))
+    }
+
+    fn is_listed(&self, (ip, port): &(String, u32)) -> bool {
+        self.servers.iter().any(|server| &server.host == ip && server.port == *port as i64)
+    }
+}
+
+// Diff every watched server against the previous snapshot. A listed server without
+// details failed to fetch rather than went offline, so it is skipped.
+fn diff_snapshots(previous: &Snapshot, current: &mut Snapshot) {
+    let mut checked = Vec::new();
+    for key in previous.detailed.keys().chain(current.detailed.keys()) {
+        if checked.contains(&key) {
+            continue;
+        }
+        checked.push(key);
+
+        let old = previous.detailed.get(key);
+        let new = current.detailed.get(key);
+        if (old.is_none() && previous.is_listed(key)) || (new.is_none() && current.is_listed(key)) {
+            continue;
+        }
+
+        let changes = diff(old, new);
+        if changes.is_empty() {
+            continue;
+        }
+
+        // A match caught in intermission has its final scores in the new snapshot
+        if let (Some(old), true) = (old, changes.iter().any(|event| matches!(event, ServerEvent::MatchEnded { .. }))) {
+            let final_state = match new {
+                Some(new) if new.timeLeft <= 0 && new.mapName == old.mapName && new.gameMode == old.gameMode => new,
+                _ => old
+            };
+            current.finished.insert(key.clone(), final_state.clone());
+        }
+
+        current.events.insert(key.clone(), changes);
+    }
+}
+
+// Broadcasts every snapshot and keeps the latest one for commands
+pub struct SnapshotBus {
+    sender: broadcast::Sender<Arc<Snapshot>>,
+    latest: RwLock<Option<Arc<Snapshot>>>,
+}
+
+impl SnapshotBus {
+    pub fn new() -> Self {
+        SnapshotBus {
+            sender: broadcast::channel(SNAPSHOT_BACKLOG).0,
+            latest: RwLock::new(None),
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<Snapshot>> {
+        self.sender.subscribe()
+    }
+
+    pub fn latest(&self) -> Option<Arc<Snapshot>> {
+        self.latest.read().unwrap().clone()
+    }
+
+    fn publish(&self, snapshot: Snapshot) {
+        let snapshot = Arc::new(snapshot);
+        *self.latest.write().unwrap() = Some(snapshot.clone());
+
+        // Sending only fails when nothing is subscribed
+        let _ = self.sender.send(snapshot);
+    }
+}
+
+impl Default for SnapshotBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Wait for the next snapshot, skipping any a slow subscriber missed
+pub async fn next_snapshot(receiver: &mut broadcast::Receiver<Arc<Snapshot>>) -> Option<Arc<Snapshot>> {
+    loop {
+        match receiver.recv().await {
+            Ok(snapshot) => return Some(snapshot),
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
+// Poll the server list, plus detailed info for bookmarked servers, and publish it
+pub async fn run(bus: Arc<SnapshotBus>, database: sqlx::MySqlPool, client: reqwest::Client) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut resolved: HashMap<String, (std::time::Instant, String)> = HashMap::new();
+
+    loop {
+        interval.tick().await;
+
+        let servers = match get_server_list(&client).await {
+            Ok(servers) => servers,
+            Err(e) => {
+                println!("[ ERROR ] Server poll failed: {e}");
+                continue;
+            }
+        };
+
+        // Every bookmarked server is watched
+        let watched = sqlx::query!("SELECT DISTINCT host, port FROM server_bookmarks")
+            .fetch_all(&database)
+            .await
+            .unwrap_or_default();
+
+        let mut addresses: HashMap<String, String> = HashMap::new();
+        let permits = Arc::new(Semaphore::new(POLL_PARALLELISM));
+        let mut requests = JoinSet::new();
+        for server in watched {
+            let ip = cached_address(&mut resolved, &server.host).await;
+            addresses.insert(server.host.clone(), ip.clone());
+
+            // Servers missing from the list are offline
+            if !servers.iter().any(|listed| listed.host == ip && listed.port == server.port as i64) {
+                continue;
+            }
+
+            let client = client.clone();
+            let permits = permits.clone();
+            requests.spawn(async move {
+                let _permit = permits.acquire_owned().await;
+                let detailed = fetch_detailed_server(&client, ip.clone(), server.port).await;
+                ((ip, server.port), detailed)
+            });
+        }
+
+        // Map subscriptions keep their server after its bookmark is deleted
+        let subscribed = sqlx::query!("SELECT DISTINCT host AS `host!` FROM map_subscriptions WHERE host IS NOT NULL")
+            .fetch_all(&database)
+            .await
+            .unwrap_or_default();
+        for sub in subscribed {
+            if !addresses.contains_key(&sub.host) {
+                let ip = cached_address(&mut resolved, &sub.host).await;
+                addresses.insert(sub.host, ip);
+            }
+        }
+
+        // Forget hosts no longer bookmarked or subscribed to
+        resolved.retain(|host, _| addresses.contains_key(host));
+
+        let mut detailed: HashMap<(String, u32), DetailedServer> = HashMap::new();
+        while let Some(res) = requests.join_next().await {
+            if let Ok((key, Ok(server))) = res {
+                detailed.insert(key, server);
+            }
+        }
+
+        let mut snapshot = Snapshot {
+            fetched_at: std::time::Instant::now(),
+            servers,
+            detailed,
+            addresses,
+            events: HashMap::new(),
+            finished: HashMap::new(),
+        };
+        if let Some(previous) = bus.latest() {
+            diff_snapshots(&previous, &mut snapshot);
+        }
+
+        bus.publish(snapshot);
+    }
+}
+
+// IP for a host, resolved again once ADDRESS_TTL has passed
+async fn cached_address(resolved: &mut HashMap<String, (std::time::Instant, String)>, host: &String) -> String {
+    match resolved.get(host) {
+        Some((resolved_at, ip)) if resolved_at.elapsed() < ADDRESS_TTL => ip.clone(),
+        _ => {
+            let ip = resolve_ip(host.clone()).await.unwrap_or(host.clone());
+            resolved.insert(host.clone(), (std::time::Instant::now(), ip.clone()));
+            ip
+        }
+    }
+}
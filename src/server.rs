@@ -1,8 +1,31 @@
-use crate::{Context, Error};
-use crate::data::{grab_api_data, resolve_ip, ServerPlayer, DetailedServer, BasicServer, TEAMMODES};
+use crate::{Context, Data, Error};
+use crate::data::{edit_distance, escape_markdown, fuzzy_rank, grab_api_data, has_clantag, option_value, paginate, parse_address, resolve_ip, ServerPlayer, DetailedServer, BasicServer, TEAMMODES};
 use crate::admin::info_role;
+use crate::poller::Snapshot;
 use serde_json::Value;
 use poise::serenity_prelude as serenity;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+// How long the shared server list is reused before fetching it again
+const SERVER_LIST_TTL: std::time::Duration = std::time::Duration::from_secs(45);
+
+// Minimum time between refreshes of the same server embed
+const REFRESH_COOLDOWN: std::time::Duration = std::time::Duration::from_secs(10);
+
+// Servers queried at once for player countries in /online
+const COUNTRY_PARALLELISM: usize = 4;
+
+// Discord's limit for one embed field
+const FIELD_LIMIT: usize = 1024;
+
+// Characters all scoreboard tables share, leaving room for spectators under Discord's 6000 character embed limit
+const SCOREBOARD_BUDGET: usize = 4000;
+
+// Code block fences and the count of hidden players around each table
+const TABLE_OVERHEAD: usize = 32;
 
 //--------------------
 // Commands
@@ -16,22 +39,11 @@ use poise::serenity_prelude as serenity;
 pub async fn listservers(ctx: Context<'_>) -> Result<(), Error> {
     ctx.defer().await?;
 
-    let api_link = String::from("https://sauertracker.net/api/v2/servers");
-    let page_url = String::from("https://sauertracker.net");
-
-    let server_data = match grab_api_data(&ctx.data().client, api_link, &page_url).await {
+    let mut server_vec = match get_server_list(&ctx.data().client).await {
         Ok(data) => data,
         Err(err) => return Err(err),
     };
-
-    let mut server_vec: Vec<BasicServer> = Vec::new();
-    for server in server_data.as_array().unwrap() {
-        if server["clients"].as_i64().unwrap() == 0 || server["version"].as_i64().unwrap() < 260 {
-            continue;
-        }
-
-        server_vec.push(serde_json::from_value(server.clone()).unwrap());
-    }
+    server_vec.retain(is_active_server);
 
     server_vec.sort_by(|a, b| b.clients.cmp(&a.clients));
     server_vec.truncate(10);
@@ -64,7 +76,185 @@ pub async fn listservers(ctx: Context<'_>) -> Result<(), Error> {
 
     //println!("{:#?}", server_vec);
 
-    ctx.say(server_list).await?;
+    if server_vec.is_empty() {
+        ctx.say(server_list).await?;
+        return Ok(());
+    }
+
+    // Select menu to pull up any of the listed servers
+    let ctx_id = ctx.id();
+    let select_id = format!("{}server", ctx_id);
+    let author_id = ctx.author().id;
+
+    let reply = ctx.send(poise::CreateReply::default()
+        .content(server_list.clone())
+        .components(server_select_components(&select_id, &server_vec, false))
+    ).await?;
+
+    while let Some(press) = serenity::collector::ComponentInteractionCollector::new(ctx)
+        .filter(move |press| press.data.custom_id.starts_with(&ctx_id.to_string()) && press.user.id == author_id)
+        .timeout(std::time::Duration::from_secs(600))
+        .await
+    {
+        if press.data.custom_id != select_id {
+            continue;
+        }
+
+        let choice = match &press.data.kind {
+            serenity::ComponentInteractionDataKind::StringSelect { values } => values.first().and_then(|value| value.parse::<usize>().ok()),
+            _ => None
+        };
+        let server = match choice.and_then(|i| server_vec.get(i)) {
+            Some(server) => server,
+            None => continue
+        };
+
+        // Fetching can take longer than Discord allows for a response
+        press.defer(ctx.serenity_context()).await?;
+
+        let page_url = format!("https://sauertracker.net/server/{}/{}", server.host, server.port);
+        let followup = match get_server_info(&ctx.data().client, server.host.clone(), server.port as u32).await
+            .and_then(|data| build_server_embed(&data, None, page_url))
+        {
+            Ok(embed) => serenity::CreateInteractionResponseFollowup::new().embed(embed),
+            Err(e) => serenity::CreateInteractionResponseFollowup::new().content(e.to_string()).ephemeral(true)
+        };
+
+        press.create_followup(ctx.serenity_context(), followup).await?;
+    }
+
+    reply.edit(ctx, poise::CreateReply::default()
+        .content(server_list)
+        .components(server_select_components(&select_id, &server_vec, true))
+    ).await?;
+
+    Ok(())
+}
+
+/// Show everyone currently playing, grouped by server.
+#[poise::command(
+    slash_command,
+    user_cooldown = 10,
+    check = "info_role"
+)]
+pub async fn online(
+    ctx: Context<'_>,
+    #[description = "Country code for players. Use __ for unknown country."]
+    #[max_length = 2] country: Option<String>,
+    #[description = "Clantag worn as a name prefix or suffix"]
+    #[max_length = 15] clan: Option<String>,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+
+    let mut server_vec = match get_server_list(&ctx.data().client).await {
+        Ok(data) => data,
+        Err(err) => return Err(err),
+    };
+    server_vec.retain(is_active_server);
+    server_vec.sort_by(|a, b| b.clients.cmp(&a.clients));
+
+    // Per-player countries are only available from the detailed server info
+    let country = country.map(|code| code.to_ascii_uppercase());
+    let mut player_countries: HashMap<(String, i64), Vec<ServerPlayer>> = HashMap::new();
+    if country.is_some() {
+        let permits = Arc::new(Semaphore::new(COUNTRY_PARALLELISM));
+        let mut requests = JoinSet::new();
+        for server in &server_vec {
+            let client = ctx.data().client.clone();
+            let permits = permits.clone();
+            let (host, port) = (server.host.clone(), server.port);
+
+            requests.spawn(async move {
+                let _permit = permits.acquire_owned().await;
+                let detailed = fetch_detailed_server(&client, host.clone(), port as u32).await;
+                ((host, port), detailed)
+            });
+        }
+
+        while let Some(res) = requests.join_next().await {
+            if let Ok((key, Ok(detailed))) = res {
+                player_countries.insert(key, detailed.players);
+            }
+        }
+    }
+
+    // Filter and group players. Servers whose details couldn't be fetched are counted, since
+    // their players can't be filtered by country.
+    let mut total_players = 0;
+    let mut unanswered = 0;
+    let mut mode_totals: HashMap<String, usize> = HashMap::new();
+    let mut server_blocks: Vec<String> = Vec::new();
+    for server in &server_vec {
+        let players: Vec<&String> = match &country {
+            Some(code) => match player_countries.get(&(server.host.clone(), server.port)) {
+                Some(detailed) => detailed.iter()
+                    .filter(|player| player.country.as_deref().unwrap_or("__").eq_ignore_ascii_case(code))
+                    .map(|player| &player.name)
+                    .collect(),
+                None => {
+                    unanswered += 1;
+                    continue;
+                }
+            },
+            None => server.players.iter().collect(),
+        };
+
+        let players: Vec<&String> = match &clan {
+            Some(tag) => players.into_iter().filter(|name| has_clantag(name, tag)).collect(),
+            None => players,
+        };
+
+        if players.is_empty() {
+            continue;
+        }
+
+        total_players += players.len();
+        *mode_totals.entry(server.gameMode.clone()).or_insert(0) += players.len();
+
+        let player_list: Vec<String> = players.iter().map(|name| escape_markdown(name.to_string())).collect();
+        server_blocks.push(format!("**[{}](https://sauertracker.net/server/{}/{})** - {}/{} | {} {}\n{}\n",
+            server.description,
+            server.host,
+            server.port,
+            server.clients,
+            server.maxClients,
+            server.gameMode,
+            server.mapName,
+            player_list.join(", "),
+        ));
+    }
+
+    let unanswered_note = match unanswered {
+        0 => String::new(),
+        1 => String::from(" (1 server didn't respond)"),
+        count => format!(" ({count} servers didn't respond)")
+    };
+
+    if server_blocks.is_empty() {
+        return Err(format!("No players found matching those filters!{unanswered_note}").into());
+    }
+
+    // Format totals, then split the servers into pages
+    let mut mode_totals: Vec<(String, usize)> = mode_totals.into_iter().collect();
+    mode_totals.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    let mode_display: Vec<String> = mode_totals.iter().map(|(mode, count)| format!("{mode}: {count}")).collect();
+    let header = format!("**Players:** {} on {} servers{}\n**Modes:** {}\n\n",
+        total_players,
+        server_blocks.len(),
+        unanswered_note,
+        mode_display.join(", ")
+    );
+
+    let pages: Vec<serenity::CreateEmbed> = server_blocks.chunks(5)
+        .map(|chunk| serenity::CreateEmbed::new()
+            .colour(0xFF0000)
+            .title("Players online")
+            .url("https://sauertracker.net")
+            .description(format!("{header}{}", chunk.concat())))
+        .collect();
+
+    paginate(ctx, pages, true).await?;
+
     Ok(())
 }
 
@@ -76,34 +266,155 @@ pub async fn listservers(ctx: Context<'_>) -> Result<(), Error> {
 )]
 pub async fn server(
     ctx: Context<'_>,
-    #[description = "Server Addr"] host: String,
+    #[description = "Server Addr, host:port or /connect line"]
+    #[autocomplete = "autocomplete_host"] host: String,
     #[description = "Server Port"] port: Option<u32>,
     #[description = "Player in game"]
+    #[autocomplete = "autocomplete_server_player"]
     #[max_length = 15] username: Option<String>,
 ) -> Result<(), Error> {
     ctx.defer().await?;
-    let port = port.unwrap_or(28785_u32);
+    let (host, port) = parse_address(&host, port)?;
     let page_url = format!("https://sauertracker.net/server/{host}/{port}");
 
-    let server_data = match get_server_info(&ctx.data().client, host.clone(), port.clone()).await {
+    let server_data = match get_live_server_info(ctx.data(), host.clone(), port.clone()).await {
         Ok(data) => data,
         Err(e) => return Err(e)
     };
 
-    let server_embed = match build_server_embed(server_data, username, page_url) {
-        Ok(embed) => embed,
-        Err(e) => return Err(e)
-    };
-    ctx.send(poise::CreateReply::default().embed(server_embed)).await?;
-
-    Ok(())
+    send_server_embed(ctx, server_data, username, page_url).await
 }
 
 //--------------------
 // Functions
 //--------------------
+// Get the full server list from the tracker
+pub async fn get_server_list(client: &reqwest::Client) -> Result<Vec<BasicServer>, Error> {
+    let api_link = String::from("https://sauertracker.net/api/v2/servers");
+    let page_url = String::from("https://sauertracker.net");
+
+    let server_data = match grab_api_data(client, api_link, &page_url).await {
+        Ok(data) => data,
+        Err(err) => return Err(err),
+    };
+
+    let server_array = match server_data.as_array() {
+        Some(servers) => servers,
+        None => return Err("The server list from Sauertracker couldn't be read!".into())
+    };
+
+    // Skip entries that don't fit, like old servers missing fields
+    let server_vec: Vec<BasicServer> = server_array.iter()
+        .filter_map(|server| serde_json::from_value(server.clone()).ok())
+        .collect();
+
+    Ok(server_vec)
+}
+
+// The background poller's latest snapshot, unless it is older than SERVER_LIST_TTL
+pub fn fresh_snapshot(data: &Data) -> Option<Arc<Snapshot>> {
+    data.snapshots.latest().filter(|snapshot| snapshot.fetched_at.elapsed() < SERVER_LIST_TTL)
+}
+
+// Server list shared between invocations, refreshed when older than SERVER_LIST_TTL
+pub async fn get_cached_server_list(data: &Data) -> Result<Vec<BasicServer>, Error> {
+    // Prefer the background poller's latest snapshot
+    if let Some(snapshot) = fresh_snapshot(data) {
+        return Ok(snapshot.servers.clone());
+    }
+
+    if let Some((fetched_at, servers)) = data.server_list.lock().await.as_ref() {
+        if fetched_at.elapsed() < SERVER_LIST_TTL {
+            return Ok(servers.clone());
+        }
+    }
+
+    // Fetched without holding the lock so other lookups aren't stuck behind a slow request
+    let servers = get_server_list(&data.client).await?;
+    *data.server_list.lock().await = Some((std::time::Instant::now(), servers.clone()));
+
+    Ok(servers)
+}
+
+// Suggest players on the server picked in the host and port options
+async fn autocomplete_server_player(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let port = option_value(ctx, "port").and_then(|port| port.parse::<u32>().ok());
+    let (host, port) = match option_value(ctx, "host").map(|host| parse_address(&host, port)) {
+        Some(Ok(address)) => address,
+        _ => return Vec::new()
+    };
+
+    autocomplete_roster(ctx, host, port, partial).await
+}
+
+// Suggest live servers by description, resolving to their address
+pub async fn autocomplete_host(ctx: Context<'_>, partial: &str) -> Vec<serenity::AutocompleteChoice> {
+    let mut servers = get_cached_server_list(ctx.data()).await.unwrap_or_default();
+    servers.retain(|server| server.version >= 260);
+    servers.sort_by(|a, b| b.clients.cmp(&a.clients));
+
+    // Typed addresses match directly, otherwise match on the description
+    let (mut suggestions, rest): (Vec<BasicServer>, Vec<BasicServer>) = servers.into_iter()
+        .partition(|server| !partial.is_empty() && format!("{}:{}", server.host, server.port).starts_with(partial.trim()));
+    suggestions.extend(fuzzy_rank(partial, rest, |server| server.description.as_str()));
+    suggestions.truncate(25);
+
+    suggestions.iter()
+        .map(|server| {
+            let address = format!("{}:{}", server.host, server.port);
+            let label: String = format!("{} ({}) - {}/{}",
+                if server.description.is_empty() { &address } else { &server.description },
+                address,
+                server.clients,
+                server.maxClients
+            ).chars().take(100).collect();
+
+            serenity::AutocompleteChoice::new(label, address)
+        })
+        .collect()
+}
+
+// Suggest players from a server's live roster
+pub async fn autocomplete_roster(ctx: Context<'_>, host: String, port: u32, partial: &str) -> Vec<String> {
+    // Bookmarked hosts were already resolved by the poller
+    let host = match ctx.data().snapshots.latest().and_then(|snapshot| snapshot.addresses.get(&host).cloned()) {
+        Some(ip) => ip,
+        None => resolve_ip(host.clone()).await.unwrap_or(host)
+    };
+    let roster = get_cached_server_list(ctx.data()).await
+        .unwrap_or_default()
+        .into_iter()
+        .find(|server| server.host == host && server.port == port as i64)
+        .map(|server| server.players)
+        .unwrap_or_default();
+
+    fuzzy_rank(partial, roster, |name| name.as_str())
+}
+
+// Populated servers excluding Pre-2020 Edition
+pub fn is_active_server(server: &BasicServer) -> bool {
+    server.clients > 0 && server.version >= 260
+}
+
+// Server info from the poller when it has fresh details for the server, as it does for
+// bookmarked servers, otherwise from the tracker
+pub async fn get_live_server_info(data: &Data, host: String, port: u32) -> Result<DetailedServer, Error> {
+    if let Some(server) = fresh_snapshot(data).and_then(|snapshot| snapshot.detailed_server(&host, port).cloned()) {
+        return Ok(server);
+    }
+
+    get_server_info(&data.client, host, port).await
+}
+
 // Get server info container
 pub async fn get_server_info(client: &reqwest::Client, host: String, port: u32) -> Result<DetailedServer, Error> {
+    let host = verify_server(client, host, port).await?;
+
+    fetch_detailed_server(client, host, port).await
+}
+
+// Resolve a server address and check the tracker knows it
+pub async fn verify_server(client: &reqwest::Client, host: String, port: u32) -> Result<String, Error> {
     // Validate host
     let host = match resolve_ip(host.clone()).await {
         Some(ip) => ip,
@@ -124,7 +435,11 @@ pub async fn get_server_info(client: &reqwest::Client, host: String, port: u32)
         return Err("The server you have specified does not exist!".into());
     }
 
-    // Grab and parse data
+    Ok(host)
+}
+
+// Grab and parse detailed info for a server already known to the tracker
+pub async fn fetch_detailed_server(client: &reqwest::Client, host: String, port: u32) -> Result<DetailedServer, Error> {
     let api_url = format!("http://sauertracker.net/api/v2/server/{host}/{port}");
     let page_url = format!("https://sauertracker.net/server/{host}/{port}");
 
@@ -175,13 +490,10 @@ pub fn server_exists(server_array: &Vec<Value>, host: &String, port: u32) -> boo
     false
 }
 
-// Build server embed or player stats embed
-pub fn build_server_embed(server_data: DetailedServer, username: Option<String>, page_url: String) -> Result<serenity::CreateEmbed, Error> {
-    let mut server_embed = serenity::CreateEmbed::new()
-        .footer(serenity::CreateEmbedFooter::new(format!("/connect {} {}", server_data.host, server_data.port)));
-
-    let mut embed_desc = format!(
-        "**Players:** {}/{}\n**Mastermode:** {}\n*{} {} {}*\n\n",
+// Player count, mastermode, map and mode of a server
+pub fn server_summary(server_data: &DetailedServer) -> String {
+    format!(
+        "**Players:** {}/{}\n**Mastermode:** {}\n*{} {} {}*",
         server_data.clients,
         server_data.maxClients,
         server_data.masterMode,
@@ -192,14 +504,22 @@ pub fn build_server_embed(server_data: DetailedServer, username: Option<String>,
         } else {
             String::new()
         }
-    );
+    )
+}
+
+// Build server embed or player stats embed
+pub fn build_server_embed(server_data: &DetailedServer, username: Option<String>, page_url: String) -> Result<serenity::CreateEmbed, Error> {
+    let mut server_embed = serenity::CreateEmbed::new()
+        .footer(serenity::CreateEmbedFooter::new(format!("/connect {} {}", server_data.host, server_data.port)));
+
+    let mut embed_desc = format!("{}\n\n", server_summary(server_data));
 
     // If username not specified, show full server otherwise user stats
     if username.is_none() {
         // Basic embed info
         server_embed = server_embed
             .colour(0xFF0000)
-            .title(server_data.description)
+            .title(server_data.description.clone())
             .url(page_url)
             .description(embed_desc);
 
@@ -219,7 +539,7 @@ pub fn build_server_embed(server_data: DetailedServer, username: Option<String>,
             }
         } else {
             let mut players_display = String::new();
-            for player in &server_data.all_active_players.unwrap() {
+            for player in &server_data.all_active_players.clone().unwrap() {
                 players_display = format!("{}{}\n", players_display, player);
             }
 
@@ -240,8 +560,8 @@ pub fn build_server_embed(server_data: DetailedServer, username: Option<String>,
         }
     } else {
         // Check if player is in server
-        let mut player_stats: Option<ServerPlayer> = None;
-        for player in server_data.players {
+        let mut player_stats: Option<&ServerPlayer> = None;
+        for player in &server_data.players {
             if player.name == username.clone().unwrap() {
                 player_stats = Some(player);
                 break;
@@ -259,7 +579,7 @@ pub fn build_server_embed(server_data: DetailedServer, username: Option<String>,
 
         server_embed = server_embed
             .colour(0xFF0000)
-            .title(server_data.description)
+            .title(server_data.description.clone())
             .url(page_url)
             .description(embed_desc)
 
@@ -272,4 +592,466 @@ pub fn build_server_embed(server_data: DetailedServer, username: Option<String>,
     }
 
     Ok(server_embed)
-}
\ No newline at end of file
+}
+
+// Build a per-player stats table for the whole server
+pub fn build_scoreboard_embed(server_data: &DetailedServer, page_url: String) -> serenity::CreateEmbed {
+    let flag_mode = is_flag_mode(&server_data.gameMode);
+
+    let mut server_embed = serenity::CreateEmbed::new()
+        .colour(0xFF0000)
+        .title(server_data.description.clone())
+        .url(page_url)
+        .description(format!("{}\n", server_summary(server_data)))
+        .footer(serenity::CreateEmbedFooter::new(format!("/connect {} {}", server_data.host, server_data.port)));
+
+    let mut active_players: Vec<&ServerPlayer> = server_data.players.iter().filter(|player| player.state != 5).collect();
+    active_players.sort_by(|a, b| {
+        if flag_mode {
+            b.flags.cmp(&a.flags).then(b.frags.cmp(&a.frags))
+        } else {
+            b.frags.cmp(&a.frags).then(a.deaths.cmp(&b.deaths))
+        }
+    });
+
+    // Group by team in team modes, continuing long tables in more fields
+    let mut budget = SCOREBOARD_BUDGET;
+    if TEAMMODES.contains(&server_data.gameMode.as_str()) {
+        for team in &server_data.teams {
+            let team_players: Vec<&ServerPlayer> = active_players.iter()
+                .filter(|player| player.team == team.name)
+                .copied()
+                .collect();
+
+            for (i, table) in format_scoreboard(&team_players, flag_mode, &mut budget).into_iter().enumerate() {
+                let title = if i == 0 { format!("{}: [{}]", team.name, team.score) } else { format!("{} (cont.)", team.name) };
+                server_embed = server_embed.field(title, table, false);
+            }
+        }
+    } else {
+        for (i, table) in format_scoreboard(&active_players, flag_mode, &mut budget).into_iter().enumerate() {
+            let title = if i == 0 { "Scoreboard:" } else { "Scoreboard (cont.):" };
+            server_embed = server_embed.field(title, table, false);
+        }
+    }
+
+    let spectators = server_data.spectators.clone().unwrap_or_default();
+    if !spectators.is_empty() {
+        let mut spectator_list = String::new();
+        for (i, name) in spectators.iter().enumerate() {
+            let name = escape_markdown(name.clone());
+            if spectator_list.len() + name.len() + TABLE_OVERHEAD > FIELD_LIMIT {
+                spectator_list = format!("{spectator_list} *...and {} more*", spectators.len() - i);
+                break;
+            }
+            spectator_list = if i == 0 { name } else { format!("{spectator_list}, {name}") };
+        }
+
+        server_embed = server_embed.field("Spectators:", spectator_list, false);
+    }
+
+    server_embed
+}
+
+// Monospace tables of player stats, split so each fits in an embed field. Players past
+// the shared budget are only counted.
+fn format_scoreboard(players: &[&ServerPlayer], flag_mode: bool, budget: &mut usize) -> Vec<String> {
+    let header = if flag_mode {
+        format!("{:<15} {:>5} {:>5} {:>6} {:>4} {:>4}\n", "Name", "Flags", "Frags", "Deaths", "Acc", "Ping")
+    } else {
+        format!("{:<15} {:>5} {:>6} {:>5} {:>4} {:>4}\n", "Name", "Frags", "Deaths", "KpD", "Acc", "Ping")
+    };
+
+    let mut tables: Vec<String> = Vec::new();
+    let mut table = header.clone();
+    *budget = budget.saturating_sub(header.len());
+    let mut shown = 0;
+    for player in players {
+        // Backticks would close the code block early
+        let name = player.name.replace('`', "'");
+
+        let row = if flag_mode {
+            format!("{:<15} {:>5} {:>5} {:>6} {:>3}% {:>4}\n", name, player.flags, player.frags, player.deaths, player.acc, player.ping)
+        } else {
+            format!("{:<15} {:>5} {:>6} {:>5.2} {:>3}% {:>4}\n", name, player.frags, player.deaths, player.kpd, player.acc, player.ping)
+        };
+
+        if table.len() + row.len() + TABLE_OVERHEAD > FIELD_LIMIT {
+            if header.len() + row.len() > *budget {
+                break;
+            }
+            tables.push(table);
+            table = header.clone();
+            *budget -= header.len();
+        }
+        if row.len() > *budget {
+            break;
+        }
+
+        *budget -= row.len();
+        table.push_str(&row);
+        shown += 1;
+    }
+    tables.push(table);
+
+    let mut tables: Vec<String> = tables.into_iter().map(|table| format!("```\n{table}```")).collect();
+    if shown < players.len() {
+        let last = tables.len() - 1;
+        tables[last] = format!("{}\n*...and {} more*", tables[last], players.len() - shown);
+    }
+
+    tables
+}
+
+// Modes scored by flags rather than frags
+pub fn is_flag_mode(mode: &str) -> bool {
+    mode.contains("ctf") || mode.contains("protect") || mode.contains("hold") || mode.contains("collect")
+}
+
+// Send a server embed with buttons to refresh it, switch views and pick players
+pub async fn send_server_embed(ctx: Context<'_>, server_data: DetailedServer, username: Option<String>, page_url: String) -> Result<(), Error> {
+    let ctx_id = ctx.id();
+    let refresh_button_id = format!("{}refresh", ctx_id);
+    let scoreboard_button_id = format!("{}scoreboard", ctx_id);
+    let player_select_id = format!("{}player", ctx_id);
+
+    // Resolve the requested player to an exact in-game name
+    let mut prompt = None;
+    let mut username = match username {
+        Some(name) => match match_player(&server_data.players, &name) {
+            PlayerMatch::Found(i) => Some(server_data.players[i].name.clone()),
+            PlayerMatch::Multiple(candidates) => {
+                let (handle, choice) = choose_player(ctx, &server_data, candidates).await?;
+                prompt = Some(handle);
+                Some(choice)
+            },
+            PlayerMatch::NotFound(closest) => {
+                let mut msg = format!("Player \"{}\" was not found in the server!", name);
+                if let Some(best) = closest.first() {
+                    msg = format!("{msg} Did you mean \"{best}\"?\nClosest names: {}", closest.join(", "));
+                }
+
+                return Err(msg.into());
+            }
+        },
+        None => None
+    };
+
+    let mut server_data = server_data;
+    let mut show_scoreboard = false;
+    let mut current_embed = build_server_embed(&server_data, username.clone(), page_url.clone())?;
+
+    let reply = poise::CreateReply::default()
+        .content(String::new())
+        .embed(current_embed.clone())
+        .components(server_components(ctx_id, &server_data, show_scoreboard, false));
+    let reply = match prompt {
+        Some(handle) => {
+            handle.edit(ctx, reply).await?;
+            handle
+        },
+        None => ctx.send(reply).await?
+    };
+
+    // Refreshes are limited per message so the tracker isn't spammed
+    let mut last_refresh = std::time::Instant::now();
+
+    while let Some(press) = serenity::collector::ComponentInteractionCollector::new(ctx)
+        .filter(move |press| press.data.custom_id.starts_with(&ctx_id.to_string()))
+        .timeout(std::time::Duration::from_secs(600))
+        .await
+    {
+        if press.data.custom_id == refresh_button_id {
+            let wait = REFRESH_COOLDOWN.saturating_sub(last_refresh.elapsed());
+            if !wait.is_zero() {
+                respond_ephemeral(ctx, &press, format!("This server was just refreshed! Try again in {} seconds.", wait.as_secs() + 1)).await?;
+                continue;
+            }
+
+            match get_server_info(&ctx.data().client, server_data.host.clone(), server_data.port as u32).await {
+                Ok(data) => {
+                    server_data = data;
+                    last_refresh = std::time::Instant::now();
+                },
+                Err(e) => {
+                    respond_ephemeral(ctx, &press, e.to_string()).await?;
+                    continue;
+                }
+            }
+
+            // The selected player may have left since the last update
+            if username.as_ref().is_some_and(|name| !server_data.players.iter().any(|player| &player.name == name)) {
+                username = None;
+            }
+        } else if press.data.custom_id == scoreboard_button_id {
+            show_scoreboard = !show_scoreboard;
+        } else if press.data.custom_id == player_select_id {
+            let choice = match &press.data.kind {
+                serenity::ComponentInteractionDataKind::StringSelect { values } => values.first().cloned(),
+                _ => None
+            };
+
+            username = choice.and_then(|value| value.parse::<usize>().ok())
+                .and_then(|i| server_data.players.get(i))
+                .map(|player| player.name.clone());
+            show_scoreboard = false;
+        } else {
+            // This is an unrelated button interaction
+            continue;
+        }
+
+        current_embed = if show_scoreboard {
+            build_scoreboard_embed(&server_data, page_url.clone())
+        } else {
+            build_server_embed(&server_data, username.clone(), page_url.clone())?
+        };
+
+        press
+            .create_response(
+                ctx.serenity_context(),
+                serenity::CreateInteractionResponse::UpdateMessage(
+                    serenity::CreateInteractionResponseMessage::new()
+                        .embed(current_embed.clone())
+                        .components(server_components(ctx_id, &server_data, show_scoreboard, false)),
+                ),
+            )
+            .await?;
+    }
+
+    // Disable the buttons once nothing is listening for them
+    reply.edit(ctx, poise::CreateReply::default()
+        .embed(current_embed)
+        .components(server_components(ctx_id, &server_data, show_scoreboard, true))
+    ).await?;
+
+    Ok(())
+}
+
+// Reply to a component press without touching the message
+async fn respond_ephemeral(ctx: Context<'_>, press: &serenity::ComponentInteraction, content: String) -> Result<(), Error> {
+    press
+        .create_response(
+            ctx.serenity_context(),
+            serenity::CreateInteractionResponse::Message(
+                serenity::CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+// Select menu over the listed servers, using their list position as the value
+fn server_select_components(select_id: &str, servers: &[BasicServer], disabled: bool) -> Vec<serenity::CreateActionRow> {
+    let options: Vec<serenity::CreateSelectMenuOption> = servers.iter()
+        .enumerate()
+        .take(25)
+        .map(|(i, server)| {
+            let label: String = format!("[{}] {}", i + 1, server.description).chars().take(100).collect();
+
+            serenity::CreateSelectMenuOption::new(label, i.to_string())
+                .description(format!("{}:{} | {}/{} | {} {}", server.host, server.port, server.clients, server.maxClients, server.gameMode, server.mapName))
+        })
+        .collect();
+
+    vec![serenity::CreateActionRow::SelectMenu(
+        serenity::CreateSelectMenu::new(select_id, serenity::CreateSelectMenuKind::String { options })
+            .placeholder("Show a server")
+            .disabled(disabled)
+    )]
+}
+
+// Result of looking up a player by a partial or misspelled name
+pub enum PlayerMatch {
+    Found(usize),
+    Multiple(Vec<usize>),
+    NotFound(Vec<String>),
+}
+
+// Find a player by exact name, then case-insensitive, then substring, then edit distance
+pub fn match_player(players: &[ServerPlayer], username: &str) -> PlayerMatch {
+    let lowered = username.to_lowercase();
+    let max_distance = (username.chars().count() / 3).max(1);
+
+    let stages: [&dyn Fn(&ServerPlayer) -> bool; 4] = [
+        &|player| player.name == username,
+        &|player| player.name.to_lowercase() == lowered,
+        &|player| player.name.to_lowercase().contains(&lowered),
+        &|player| edit_distance(&player.name.to_lowercase(), &lowered) <= max_distance,
+    ];
+
+    for stage in stages {
+        let matches: Vec<usize> = players.iter()
+            .enumerate()
+            .filter(|(_, player)| stage(player))
+            .map(|(i, _)| i)
+            .collect();
+
+        match matches.len() {
+            0 => continue,
+            1 => return PlayerMatch::Found(matches[0]),
+            _ => return PlayerMatch::Multiple(matches),
+        }
+    }
+
+    // Nothing close enough, suggest the nearest names instead
+    let mut closest: Vec<(usize, &String)> = players.iter()
+        .map(|player| (edit_distance(&player.name.to_lowercase(), &lowered), &player.name))
+        .collect();
+    closest.sort();
+
+    PlayerMatch::NotFound(closest.into_iter().take(3).map(|(_, name)| name.clone()).collect())
+}
+
+// Ask the invoker to pick one of several matching players
+async fn choose_player<'a>(ctx: Context<'a>, server_data: &DetailedServer, candidates: Vec<usize>) -> Result<(poise::ReplyHandle<'a>, String), Error> {
+    let ctx_id = ctx.id();
+    let select_id = format!("{}player", ctx_id);
+    let author_id = ctx.author().id;
+
+    let reply = ctx.send(poise::CreateReply::default()
+        .content("Several players match that name, pick one:")
+        .components(vec![serenity::CreateActionRow::SelectMenu(player_select_menu(&select_id, server_data, &candidates))])
+    ).await?;
+
+    let press = serenity::collector::ComponentInteractionCollector::new(ctx)
+        .filter(move |press| press.data.custom_id == select_id && press.user.id == author_id)
+        .timeout(std::time::Duration::from_secs(60))
+        .await;
+
+    let choice = press.as_ref().and_then(|press| match &press.data.kind {
+        serenity::ComponentInteractionDataKind::StringSelect { values } => values.first().and_then(|value| value.parse::<usize>().ok()),
+        _ => None
+    });
+
+    match (press, choice) {
+        (Some(press), Some(i)) => {
+            press.create_response(ctx.serenity_context(), serenity::CreateInteractionResponse::Acknowledge).await?;
+            Ok((reply, server_data.players[i].name.clone()))
+        },
+        _ => {
+            reply.edit(ctx, poise::CreateReply::default().content("No player was selected!").components(vec![])).await?;
+            Err("No player was selected!".into())
+        }
+    }
+}
+
+// Select menu over players, using their index as the value
+fn player_select_menu(select_id: &str, server_data: &DetailedServer, candidates: &[usize]) -> serenity::CreateSelectMenu {
+    let options: Vec<serenity::CreateSelectMenuOption> = candidates.iter()
+        .take(25)
+        .map(|&i| {
+            let player = &server_data.players[i];
+            serenity::CreateSelectMenuOption::new(player.name.clone(), i.to_string())
+                .description(format!("{} frags | {} deaths{}",
+                    player.frags,
+                    player.deaths,
+                    if player.team.is_empty() { String::new() } else { format!(" | {}", player.team) }
+                ))
+        })
+        .collect();
+
+    serenity::CreateSelectMenu::new(select_id, serenity::CreateSelectMenuKind::String { options })
+        .placeholder("Choose a player")
+}
+
+// Component rows for the server embed
+fn server_components(ctx_id: u64, server_data: &DetailedServer, show_scoreboard: bool, disabled: bool) -> Vec<serenity::CreateActionRow> {
+    let scoreboard_label = if show_scoreboard { "Compact" } else { "Scoreboard" };
+
+    let mut buttons = vec![
+        serenity::CreateButton::new(format!("{}refresh", ctx_id))
+            .label("Refresh")
+            .style(serenity::ButtonStyle::Primary)
+            .disabled(disabled),
+        serenity::CreateButton::new(format!("{}scoreboard", ctx_id))
+            .label(scoreboard_label)
+            .style(serenity::ButtonStyle::Secondary)
+            .disabled(disabled),
+    ];
+
+    // Link buttons only accept full URLs
+    if server_data.info.website.starts_with("http") {
+        buttons.push(serenity::CreateButton::new_link(server_data.info.website.clone()).label("Website"));
+    }
+    if server_data.info.demourl.starts_with("http") {
+        buttons.push(serenity::CreateButton::new_link(server_data.info.demourl.clone()).label("Demos"));
+    }
+
+    let mut components = vec![serenity::CreateActionRow::Buttons(buttons)];
+
+    // Select menus need at least one option
+    if !server_data.players.is_empty() {
+        let candidates: Vec<usize> = (0..server_data.players.len()).collect();
+        let select_menu = player_select_menu(&format!("{}player", ctx_id), server_data, &candidates)
+            .placeholder("Show a player's stats")
+            .disabled(disabled);
+
+        components.push(serenity::CreateActionRow::SelectMenu(select_menu));
+    }
+
+    components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn players(names: &[&str]) -> Vec<ServerPlayer> {
+        names.iter()
+            .map(|name| ServerPlayer { name: name.to_string(), ..Default::default() })
+            .collect()
+    }
+
+    #[test]
+    fn exact_name_wins_over_case_match() {
+        let players = players(&["Frosty", "frosty"]);
+        assert!(matches!(match_player(&players, "frosty"), PlayerMatch::Found(1)));
+    }
+
+    #[test]
+    fn case_is_ignored_without_an_exact_match() {
+        let players = players(&["Frosty", "Raven"]);
+        assert!(matches!(match_player(&players, "FROSTY"), PlayerMatch::Found(0)));
+    }
+
+    #[test]
+    fn partial_name_matches() {
+        let players = players(&["[TAG]Frosty", "Raven"]);
+        assert!(matches!(match_player(&players, "frost"), PlayerMatch::Found(0)));
+    }
+
+    #[test]
+    fn ties_are_returned_together() {
+        let players = players(&["Frosty1", "Raven", "Frosty2"]);
+        match match_player(&players, "frosty") {
+            PlayerMatch::Multiple(candidates) => assert_eq!(candidates, vec![0, 2]),
+            _ => panic!("expected several matches")
+        }
+    }
+
+    #[test]
+    fn typos_match_within_distance() {
+        let players = players(&["Frosty", "Raven"]);
+        assert!(matches!(match_player(&players, "frsoty"), PlayerMatch::Found(0)));
+    }
+
+    #[test]
+    fn no_match_suggests_closest_names() {
+        let players = players(&["Frosty", "Raven", "Ravenous", "Zed"]);
+        match match_player(&players, "quixotic") {
+            PlayerMatch::NotFound(closest) => assert_eq!(closest.len(), 3),
+            _ => panic!("expected no match")
+        }
+    }
+
+    #[test]
+    fn empty_server_finds_nothing() {
+        match match_player(&[], "frosty") {
+            PlayerMatch::NotFound(closest) => assert!(closest.is_empty()),
+            _ => panic!("expected no match")
+        }
+    }
+}
@@ -0,0 +1,340 @@
+use crate::{Context, Error};
+use crate::admin::info_role;
+use crate::alerts::{alert_channel, can_manage_channels, AlertTarget};
+use crate::dispatch::{notify, Notification};
+use crate::bookmark::{autocomplete_bookmark, find_bookmark_address};
+use crate::data::{escape_markdown, fuzzy_rank, GameMode, MapSubscription, MODENAMES};
+use crate::poller::Snapshot;
+use crate::server::get_cached_server_list;
+use poise::serenity_prelude as serenity;
+use std::collections::HashSet;
+
+// Players a game needs when no minimum is given
+const DEFAULT_SUBSCRIPTION_PLAYERS: u32 = 2;
+
+// Subscriptions held at once by a user's DMs and by a guild
+const MAX_USER_SUBSCRIPTIONS: usize = 10;
+const MAX_GUILD_SUBSCRIPTIONS: usize = 25;
+
+/// Get notified when games on a map or mode start
+#[poise::command(
+    slash_command,
+    subcommands("add", "remove", "list"),
+    subcommand_required
+)]
+pub async fn mapsub(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Subscribe to games on a map, a mode or both
+#[poise::command(
+    slash_command,
+    check = "info_role",
+    user_cooldown = 10
+)]
+pub async fn add(
+    ctx: Context<'_>,
+    #[description = "Map name"]
+    #[autocomplete = "autocomplete_map"]
+    #[max_length = 64] map: Option<String>,
+    #[description = "Game mode"]
+    #[autocomplete = "autocomplete_mode"] mode: Option<String>,
+    #[description = "Only games on this bookmarked server"]
+    #[autocomplete = "autocomplete_bookmark"] bookmark: Option<String>,
+    #[description = "Players a game needs (default 2)"]
+    #[min = 1]
+    #[max = 128] min_players: Option<u32>,
+    #[description = "Post alerts in this channel instead of sending you a DM"]
+    #[channel_types("Text", "News")] channel: Option<serenity::GuildChannel>,
+) -> Result<(), Error> {
+    if map.is_none() && mode.is_none() {
+        return Err("Give a map, a mode or both!".into());
+    }
+
+    let map = map.map(|map| map.trim().to_lowercase());
+    let mode = match mode {
+        Some(mode) => Some(mode.parse::<GameMode>()?),
+        None => None
+    };
+    let min_players = min_players.unwrap_or(DEFAULT_SUBSCRIPTION_PLAYERS);
+
+    let database = &ctx.data().database;
+    let user_id = ctx.author().id.get();
+    let channel_id = alert_channel(ctx, channel.as_ref()).await?;
+    let guild_id = channel.as_ref().map(|channel| channel.guild_id.get());
+
+    let (host, port) = match &bookmark {
+        Some(bookmark) => match find_bookmark_address(database, ctx.guild_id().map(|id| id.get()), user_id, bookmark).await {
+            Some((host, port)) => (Some(host), Some(port)),
+            None => return Err("No bookmark with that name exists!".into())
+        },
+        None => (None, None)
+    };
+
+    let (existing, limit) = match guild_id {
+        Some(guild_id) => (
+            sqlx::query_as!(MapSubscription, "SELECT * FROM map_subscriptions WHERE guild_id = ?", guild_id)
+                .fetch_all(database)
+                .await
+                .unwrap(),
+            MAX_GUILD_SUBSCRIPTIONS
+        ),
+        None => (
+            sqlx::query_as!(MapSubscription, "SELECT * FROM map_subscriptions WHERE user_id = ? AND channel_id IS NULL", user_id)
+                .fetch_all(database)
+                .await
+                .unwrap(),
+            MAX_USER_SUBSCRIPTIONS
+        )
+    };
+
+    if existing.len() >= limit {
+        return Err(format!("At most {} subscriptions can be made at once!", limit).into());
+    }
+
+    let game_mode = mode.map(|mode| mode.name().to_string());
+    let duplicate = existing.iter().any(|sub| {
+        sub.channel_id == channel_id && sub.map_name == map && sub.game_mode == game_mode && sub.host == host && sub.port == port
+    });
+    if duplicate {
+        return Err("That subscription already exists!".into());
+    }
+
+    sqlx::query!("INSERT INTO map_subscriptions (user_id, guild_id, channel_id, map_name, game_mode, host, port, min_players) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        user_id, guild_id, channel_id, map, game_mode, host, port, min_players)
+        .execute(database)
+        .await
+        .unwrap();
+
+    let games = describe_games(map.as_deref(), game_mode.as_deref());
+    let reply = match &channel {
+        Some(channel) => format!("{}, alerts will be posted in {} when {} starts with {} or more players!", ctx.author(), channel, games, min_players),
+        None => format!("{}, you'll get a DM when {} starts with {} or more players! Make sure the bot can send you DMs.", ctx.author(), games, min_players)
+    };
+    ctx.send(poise::CreateReply::default()
+        .content(reply)
+        .ephemeral(channel.is_none())
+    ).await?;
+
+    Ok(())
+}
+
+/// Remove a map or mode subscription
+#[poise::command(
+    slash_command,
+    check = "info_role",
+    user_cooldown = 10
+)]
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "Subscription to remove"]
+    #[autocomplete = "autocomplete_subscription"] subscription: i32,
+) -> Result<(), Error> {
+    let sub = match sqlx::query_as!(MapSubscription, "SELECT * FROM map_subscriptions WHERE id = ?", subscription)
+        .fetch_optional(&ctx.data().database)
+        .await
+        .unwrap()
+    {
+        Some(sub) => sub,
+        None => return Err("No subscription with that ID exists!".into())
+    };
+
+    // DM subscriptions belong to their user, channel ones to the guild's channel managers
+    let allowed = match sub.channel_id {
+        Some(_) => sub.guild_id.is_some() && sub.guild_id == ctx.guild_id().map(|id| id.get()) && can_manage_channels(ctx).await,
+        None => sub.user_id == ctx.author().id.get()
+    };
+    if !allowed {
+        return Err("You can't remove that subscription!".into());
+    }
+
+    sqlx::query!("DELETE FROM map_subscriptions WHERE id = ?", sub.id)
+        .execute(&ctx.data().database)
+        .await
+        .unwrap();
+
+    ctx.send(poise::CreateReply::default()
+        .content(format!("{}, removed the subscription to {}!", ctx.author(), describe_subscription(&sub)))
+        .ephemeral(sub.channel_id.is_none())
+    ).await?;
+
+    Ok(())
+}
+
+/// List your map and mode subscriptions, and this server's
+#[poise::command(
+    slash_command,
+    check = "info_role",
+    user_cooldown = 10
+)]
+pub async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let subs = visible_subscriptions(ctx).await;
+    if subs.is_empty() {
+        return Err("There are no subscriptions! Add one with /mapsub add.".into());
+    }
+
+    let mut desc = String::new();
+    for sub in &subs {
+        let target = match sub.channel_id {
+            Some(channel_id) => format!("<#{channel_id}>"),
+            None => String::from("DM")
+        };
+        desc = format!("{desc}- `#{}` {} - {}\n", sub.id, describe_subscription(sub), target);
+    }
+
+    let embed = serenity::CreateEmbed::new()
+        .colour(0xFF0000)
+        .title("Map and mode subscriptions")
+        .description(desc);
+
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+// Alert once for each server that starts a subscribed game with enough players. A server
+// stays alerted for until it stops running a matching game. Games already running when a
+// subscription is first seen, after /mapsub add or a restart, are recorded without alerting.
+pub async fn check_map_subscriptions(database: &sqlx::MySqlPool, snapshot: &Snapshot, seen: &mut HashSet<i32>) {
+    let subs: Vec<MapSubscription> = sqlx::query_as!(MapSubscription, "SELECT * FROM map_subscriptions")
+        .fetch_all(database)
+        .await
+        .unwrap_or_default();
+    seen.retain(|id| subs.iter().any(|sub| sub.id == *id));
+    if subs.is_empty() {
+        return;
+    }
+
+    let alerted: HashSet<(i32, String)> = sqlx::query!("SELECT subscription_id, server FROM map_subscription_games")
+        .fetch_all(database)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|game| (game.subscription_id, game.server))
+        .collect();
+
+    for sub in subs {
+        let seeding = seen.insert(sub.id);
+
+        // Bookmarks are matched by the IP the tracker lists them by
+        let scope = match (&sub.host, sub.port) {
+            (Some(host), Some(port)) => Some((snapshot.address(host), port)),
+            _ => None
+        };
+
+        let running: Vec<_> = snapshot.servers.iter()
+            .filter(|server| sub.map_name.as_ref().is_none_or(|map| server.mapName.eq_ignore_ascii_case(map)))
+            .filter(|server| sub.game_mode.as_ref().is_none_or(|mode| &server.gameMode == mode))
+            .filter(|server| scope.as_ref().is_none_or(|(ip, port)| &server.host == ip && server.port == *port as i64))
+            .collect();
+        let running_addresses: Vec<String> = running.iter().map(|server| format!("{}:{}", server.host, server.port)).collect();
+
+        for (server, address) in running.iter().zip(&running_addresses) {
+            if server.clients < sub.min_players as i64 || alerted.contains(&(sub.id, address.clone())) {
+                continue;
+            }
+
+            if !seeding {
+                let embed = serenity::CreateEmbed::new()
+                    .colour(0xFF0000)
+                    .title(format!("{} {} is being played!", server.mapName, server.gameMode))
+                    .url(format!("https://sauertracker.net/server/{}/{}", server.host, server.port))
+                    .description(format!("**Server:** {}\n**Players:** {}/{}\n**Join:** `/connect {} {}`",
+                        escape_markdown(server.description.clone()),
+                        server.clients,
+                        server.maxClients,
+                        server.host,
+                        server.port
+                    ));
+
+                let queued = notify(database, Notification {
+                    target: AlertTarget::new(sub.channel_id, sub.user_id),
+                    guild_id: sub.guild_id,
+                    key: format!("game:{}:{}:{}", address, server.mapName, server.gameMode),
+                    summary: format!("{} {} is being played on {}", server.mapName, server.gameMode, escape_markdown(server.description.clone())),
+                    message: serenity::CreateMessage::new().embed(embed),
+                }).await;
+                if !queued {
+                    continue;
+                }
+            }
+
+            let res = sqlx::query!("INSERT IGNORE INTO map_subscription_games (subscription_id, server) VALUES (?, ?)", sub.id, address)
+                .execute(database)
+                .await;
+            if let Err(e) = res {
+                println!("[ ERROR ] Recording game {address} for subscription #{} failed: {e}", sub.id);
+            }
+        }
+
+        // Forget servers that moved on so their next matching game alerts again
+        for (id, address) in &alerted {
+            if *id != sub.id || running_addresses.contains(address) {
+                continue;
+            }
+
+            let res = sqlx::query!("DELETE FROM map_subscription_games WHERE subscription_id = ? AND server = ?", id, address)
+                .execute(database)
+                .await;
+            if let Err(e) = res {
+                println!("[ ERROR ] Forgetting game {address} for subscription #{id} failed: {e}");
+            }
+        }
+    }
+}
+
+// DM subscriptions of the user, plus the guild's channel subscriptions
+async fn visible_subscriptions(ctx: Context<'_>) -> Vec<MapSubscription> {
+    let guild_id = ctx.guild_id().map(|id| id.get());
+
+    sqlx::query_as!(MapSubscription, "SELECT * FROM map_subscriptions WHERE (user_id = ? AND channel_id IS NULL) OR guild_id = ? ORDER BY id", ctx.author().id.get(), guild_id)
+        .fetch_all(&ctx.data().database)
+        .await
+        .unwrap()
+}
+
+fn describe_games(map: Option<&str>, mode: Option<&str>) -> String {
+    match (map, mode) {
+        (Some(map), Some(mode)) => format!("{map} {mode}"),
+        (Some(map), None) => format!("any mode on {map}"),
+        (None, Some(mode)) => format!("{mode} on any map"),
+        (None, None) => String::from("any game")
+    }
+}
+
+fn describe_subscription(sub: &MapSubscription) -> String {
+    let mut desc = describe_games(sub.map_name.as_deref(), sub.game_mode.as_deref());
+    if let (Some(host), Some(port)) = (&sub.host, sub.port) {
+        desc = format!("{desc} on {host}:{port}");
+    }
+
+    format!("{desc}, {}+ players", sub.min_players)
+}
+
+// Suggest maps being played right now
+async fn autocomplete_map(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let mut maps: Vec<String> = get_cached_server_list(ctx.data()).await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|server| server.mapName.to_lowercase())
+        .filter(|map| !map.is_empty())
+        .collect();
+    maps.sort();
+    maps.dedup();
+
+    fuzzy_rank(partial, maps, |map| map.as_str())
+}
+
+async fn autocomplete_mode(_ctx: Context<'_>, partial: &str) -> Vec<String> {
+    fuzzy_rank(partial, MODENAMES.iter().map(|mode| mode.to_string()).collect(), |mode| mode.as_str())
+}
+
+async fn autocomplete_subscription(ctx: Context<'_>, _partial: &str) -> Vec<serenity::AutocompleteChoice> {
+    visible_subscriptions(ctx).await
+        .iter()
+        .map(|sub| {
+            let label: String = format!("#{} {}", sub.id, describe_subscription(sub)).chars().take(100).collect();
+            serenity::AutocompleteChoice::new(label, sub.id)
+        })
+        .collect()
+}
@@ -0,0 +1,171 @@
+use crate::{Context, Error};
+use crate::alerts::AlertTarget;
+use crate::bookmark::autocomplete_bookmark;
+use crate::data::{escape_markdown, DetailedServer, ServerBookmark, TEAMMODES};
+use crate::dispatch::{notify, Notification};
+use crate::poller::Snapshot;
+use crate::server::{build_scoreboard_embed, is_flag_mode};
+use poise::serenity_prelude as serenity;
+
+// Players a game needs for its summary to be posted when no minimum is given
+const DEFAULT_SUMMARY_PLAYERS: u32 = 4;
+
+/// Post final scoreboards when games end on bookmarked servers
+#[poise::command(
+    slash_command,
+    subcommands("set", "disable"),
+    subcommand_required,
+    guild_only
+)]
+pub async fn matchsummary(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Post the final scoreboard of every game on a bookmarked server
+#[poise::command(
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS",
+    guild_only
+)]
+pub async fn set(
+    ctx: Context<'_>,
+    #[description = "Name of the server bookmark"]
+    #[autocomplete = "autocomplete_bookmark"] bookmark: String,
+    #[description = "Channel to post the scoreboards in"]
+    #[channel_types("Text", "News")] channel: serenity::GuildChannel,
+    #[description = "Players a game needs to be posted (default 4)"]
+    #[min = 1]
+    #[max = 128] min_players: Option<u32>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let min_players = min_players.unwrap_or(DEFAULT_SUMMARY_PLAYERS);
+
+    let bookmark_info = match sqlx::query_as!(ServerBookmark, "SELECT * FROM server_bookmarks WHERE guild_id = ? AND bookmark_name = ?", guild_id, bookmark)
+        .fetch_optional(&ctx.data().database)
+        .await
+        .unwrap()
+    {
+        Some(info) => info,
+        None => return Err("No bookmark with that name exists!".into())
+    };
+
+    sqlx::query!("INSERT INTO match_summaries (bookmark_id, channel_id, min_players) VALUES (?, ?, ?)
+        ON DUPLICATE KEY UPDATE channel_id = VALUES(channel_id), min_players = VALUES(min_players)",
+        bookmark_info.id, channel.id.get(), min_players)
+        .execute(&ctx.data().database)
+        .await
+        .unwrap();
+
+    let msg = format!("{}, games with {} or more players on {} will be posted in {}!", ctx.author(), min_players, bookmark, channel);
+    ctx.say(msg).await?;
+
+    Ok(())
+}
+
+/// Stop posting final scoreboards for a bookmarked server
+#[poise::command(
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS",
+    guild_only
+)]
+pub async fn disable(
+    ctx: Context<'_>,
+    #[description = "Name of the server bookmark"]
+    #[autocomplete = "autocomplete_bookmark"] bookmark: String,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+
+    let deleted = sqlx::query!("DELETE match_summaries FROM match_summaries JOIN server_bookmarks ON server_bookmarks.id = match_summaries.bookmark_id WHERE server_bookmarks.guild_id = ? AND server_bookmarks.bookmark_name = ?", guild_id, bookmark)
+        .execute(&ctx.data().database)
+        .await
+        .unwrap();
+
+    if deleted.rows_affected() == 0 {
+        return Err("Scoreboards aren't being posted for that bookmark!".into());
+    }
+
+    let msg = format!("{}, stopped posting scoreboards for {}!", ctx.author(), bookmark);
+    ctx.say(msg).await?;
+
+    Ok(())
+}
+
+// Post the final scoreboard of games that ended since the previous snapshot
+pub async fn check_match_summaries(database: &sqlx::MySqlPool, snapshot: &Snapshot) {
+    if snapshot.finished.is_empty() {
+        return;
+    }
+
+    let summaries = sqlx::query!("SELECT match_summaries.channel_id, match_summaries.min_players, server_bookmarks.guild_id, bookmark_name, host, port, emoji
+        FROM match_summaries JOIN server_bookmarks ON server_bookmarks.id = match_summaries.bookmark_id")
+        .fetch_all(database)
+        .await
+        .unwrap_or_default();
+
+    for summary in summaries {
+        let server = match snapshot.finished_match(&summary.host, summary.port) {
+            Some(server) => server,
+            None => continue
+        };
+
+        let players = server.players.iter().filter(|player| player.state != 5).count() as u32;
+        if players < summary.min_players {
+            continue;
+        }
+
+        let name = format!("{}{}",
+            summary.emoji.as_ref().map(|emoji| format!("{emoji} ")).unwrap_or_default(),
+            summary.bookmark_name
+        );
+        let page_url = format!("https://sauertracker.net/server/{}/{}", summary.host, summary.port);
+        let embed = build_match_summary_embed(&name, server, page_url);
+
+        // Games on the same map and mode only differ by their scores
+        let frags: i64 = server.players.iter().map(|player| player.frags).sum();
+        notify(database, Notification {
+            target: AlertTarget::Channel(summary.channel_id),
+            guild_id: Some(summary.guild_id),
+            key: format!("summary:{}:{}:{}:{}:{}", summary.host, summary.port, server.mapName, server.gameMode, frags),
+            summary: format!("Game of {} {} ended on {}", server.mapName, server.gameMode, name),
+            message: serenity::CreateMessage::new().embed(embed),
+        }).await;
+    }
+}
+
+// Final scoreboard with the result, top fragger and flag leader above it
+fn build_match_summary_embed(bookmark_name: &str, server_data: &DetailedServer, page_url: String) -> serenity::CreateEmbed {
+    let active_players = server_data.players.iter().filter(|player| player.state != 5);
+
+    let mut summary = format!("*{} {}*\n", server_data.mapName, server_data.gameMode);
+
+    if TEAMMODES.contains(&server_data.gameMode.as_str()) && !server_data.teams.is_empty() {
+        let mut teams = server_data.teams.clone();
+        teams.sort_by(|a, b| b.score.cmp(&a.score));
+
+        let result = teams.iter()
+            .map(|team| format!("{} **{}**", team.name, team.score))
+            .collect::<Vec<String>>()
+            .join(" - ");
+        let outcome = if teams.len() > 1 && teams[0].score == teams[1].score {
+            String::from("Draw")
+        } else {
+            format!("{} wins", teams[0].name)
+        };
+
+        summary = format!("{summary}**Result:** {result} ({outcome})\n");
+    }
+
+    if let Some(player) = active_players.clone().max_by_key(|player| player.frags) {
+        summary = format!("{summary}**Top fragger:** {} ({} frags)\n", escape_markdown(player.name.clone()), player.frags);
+    }
+
+    if is_flag_mode(&server_data.gameMode) {
+        if let Some(player) = active_players.max_by_key(|player| player.flags).filter(|player| player.flags > 0) {
+            summary = format!("{summary}**Flag leader:** {} ({} flags)\n", escape_markdown(player.name.clone()), player.flags);
+        }
+    }
+
+    build_scoreboard_embed(server_data, page_url)
+        .title(format!("Game ended on {}", bookmark_name))
+        .description(summary)
+}
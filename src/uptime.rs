@@ -0,0 +1,358 @@
+use crate::{Context, Error};
+use crate::admin::info_role;
+use crate::alerts::AlertTarget;
+use crate::bookmark::autocomplete_bookmark;
+use crate::data::ServerBookmark;
+use crate::dispatch::{notify, Notification};
+use crate::poller::{next_snapshot, Snapshot, SnapshotBus};
+use poise::serenity_prelude as serenity;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+// Polls in a row a server must be unreachable before it counts as down
+const OUTAGE_AFTER_MISSES: u32 = 2;
+
+// How long to wait for a server to answer a direct query, and how often to ask
+const QUERY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+const QUERY_ATTEMPTS: u32 = 2;
+
+// Unlisted servers queried at once
+const QUERY_PARALLELISM: usize = 4;
+
+// Outages listed by /uptime
+const RECENT_OUTAGES: usize = 5;
+
+/// Show how reliably a bookmarked server has been up
+#[poise::command(
+    slash_command,
+    check = "info_role",
+    user_cooldown = 10,
+    guild_only
+)]
+pub async fn uptime(
+    ctx: Context<'_>,
+    #[description = "Name of the server bookmark"]
+    #[autocomplete = "autocomplete_bookmark"] bookmark: String,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let database = &ctx.data().database;
+
+    let bookmark_info = match sqlx::query_as!(ServerBookmark, "SELECT * FROM server_bookmarks WHERE guild_id = ? AND bookmark_name = ?", guild_id, bookmark)
+        .fetch_optional(database)
+        .await
+        .unwrap()
+    {
+        Some(info) => info,
+        None => return Err("No bookmark with that name exists!".into())
+    };
+
+    let monitoring = sqlx::query!("SELECT monitored_since, NOW() AS `now!: chrono::NaiveDateTime` FROM bookmark_monitoring WHERE bookmark_id = ?", bookmark_info.id)
+        .fetch_optional(database)
+        .await
+        .unwrap();
+    let (monitored_since, now): (chrono::NaiveDateTime, chrono::NaiveDateTime) = match monitoring {
+        Some(monitoring) => (monitoring.monitored_since, monitoring.now),
+        None => return Err("That bookmark hasn't been monitored yet! Check again in a minute.".into())
+    };
+
+    let outages: Vec<(chrono::NaiveDateTime, Option<chrono::NaiveDateTime>)> = sqlx::query!("SELECT started_at, ended_at FROM server_outages WHERE bookmark_id = ? AND (ended_at IS NULL OR ended_at > NOW() - INTERVAL 30 DAY) ORDER BY started_at DESC", bookmark_info.id)
+        .fetch_all(database)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|outage| (outage.started_at, outage.ended_at))
+        .collect();
+
+    let mut desc = match outages.first() {
+        Some((started_at, None)) => format!("**Status:** Down for {}\n\n", format_duration(now.signed_duration_since(*started_at))),
+        _ => String::from("**Status:** Up\n\n")
+    };
+
+    for (label, days) in [("Last 24 hours", 1), ("Last 7 days", 7), ("Last 30 days", 30)] {
+        let start = (now - chrono::Duration::days(days)).max(monitored_since);
+        desc = match uptime_percentage(&outages, start, now) {
+            Some(percentage) => format!("{desc}**{label}:** {percentage:.2}%\n"),
+            None => format!("{desc}**{label}:** No data yet\n")
+        };
+    }
+
+    if monitored_since > now - chrono::Duration::days(30) {
+        desc = format!("{desc}*Monitored for {}.*\n", format_duration(now.signed_duration_since(monitored_since)));
+    }
+    desc = format!("{desc}*Time the bot itself was offline counts as up.*\n");
+
+    if !outages.is_empty() {
+        desc = format!("{desc}\n__**Recent outages:**__\n");
+        for (started_at, ended_at) in outages.iter().take(RECENT_OUTAGES) {
+            desc = match ended_at {
+                Some(ended_at) => format!("{desc}- {} ago, down for {}\n", format_duration(now.signed_duration_since(*started_at)), format_duration(ended_at.signed_duration_since(*started_at))),
+                None => format!("{desc}- {} ago, still down\n", format_duration(now.signed_duration_since(*started_at)))
+            };
+        }
+    }
+
+    let embed = serenity::CreateEmbed::new()
+        .colour(0xFF0000)
+        .title(format!("Uptime for {}", bookmark_info.bookmark_name))
+        .description(desc)
+        .footer(serenity::CreateEmbedFooter::new(format!("/connect {} {}", bookmark_info.host, bookmark_info.port)));
+
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
+// Share of the time between start and end a server wasn't in an outage
+fn uptime_percentage(outages: &[(chrono::NaiveDateTime, Option<chrono::NaiveDateTime>)], start: chrono::NaiveDateTime, end: chrono::NaiveDateTime) -> Option<f64> {
+    let total = (end - start).num_seconds();
+    if total <= 0 {
+        return None;
+    }
+
+    let down: i64 = outages.iter()
+        .map(|(started_at, ended_at)| {
+            let overlap = ended_at.unwrap_or(end).min(end) - (*started_at).max(start);
+            overlap.num_seconds().max(0)
+        })
+        .sum();
+
+    Some(100.0 * (total - down.min(total)) as f64 / total as f64)
+}
+
+// Rough length of time, like "2d 3h" or "5m"
+fn format_duration(duration: chrono::Duration) -> String {
+    let minutes = duration.num_minutes();
+    if minutes < 1 {
+        String::from("less than a minute")
+    } else if minutes < 60 {
+        format!("{}m", minutes)
+    } else if minutes < 60 * 24 {
+        format!("{}h {}m", minutes / 60, minutes % 60)
+    } else {
+        format!("{}d {}h", minutes / (60 * 24), minutes / 60 % 24)
+    }
+}
+
+// Track whether bookmarked servers are reachable on every snapshot, storing outages and
+// telling the guild's availability channel when one starts or ends
+pub async fn run(database: sqlx::MySqlPool, snapshots: Arc<SnapshotBus>) {
+    let mut receiver = snapshots.subscribe();
+    let mut misses: HashMap<i32, (u32, std::time::Instant)> = HashMap::new();
+
+    while let Some(snapshot) = next_snapshot(&mut receiver).await {
+        check_availability(&database, &snapshot, &mut misses).await;
+    }
+}
+
+// Misses are counted per bookmark along with when the first one happened, which is when an outage starts
+async fn check_availability(database: &sqlx::MySqlPool, snapshot: &Snapshot, misses: &mut HashMap<i32, (u32, std::time::Instant)>) {
+    let res = sqlx::query!("INSERT IGNORE INTO bookmark_monitoring (bookmark_id, monitored_since) SELECT id, NOW() FROM server_bookmarks")
+        .execute(database)
+        .await;
+    if let Err(e) = res {
+        println!("[ ERROR ] Starting bookmark monitoring failed: {e}");
+    }
+
+    let bookmarks = sqlx::query!("SELECT server_bookmarks.id, server_bookmarks.guild_id, bookmark_name, host, port, emoji, availability_channel FROM server_bookmarks LEFT JOIN guild_settings ON guild_settings.guild_id = server_bookmarks.guild_id")
+        .fetch_all(database)
+        .await
+        .unwrap_or_default();
+
+    // Open outages by bookmark, with their ID and how long the server has been down
+    let open_outages: HashMap<i32, (i32, chrono::Duration)> = sqlx::query!("SELECT id, bookmark_id, started_at, NOW() AS `now!: chrono::NaiveDateTime` FROM server_outages WHERE ended_at IS NULL")
+        .fetch_all(database)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|outage| (outage.bookmark_id, (outage.id, outage.now.signed_duration_since(outage.started_at))))
+        .collect();
+
+    // Servers missing from the tracker might only have lost their master server connection.
+    // They're queried at the IP the poller resolved, a few at a time.
+    let mut reachable: HashMap<(String, u32), bool> = HashMap::new();
+    let permits = Arc::new(Semaphore::new(QUERY_PARALLELISM));
+    let mut queries = JoinSet::new();
+    for bookmark in &bookmarks {
+        let address = (snapshot.address(&bookmark.host), bookmark.port);
+        if reachable.contains_key(&address) {
+            continue;
+        }
+
+        let listed = snapshot.server(&bookmark.host, bookmark.port).is_some();
+        reachable.insert(address.clone(), listed);
+        if !listed {
+            let permits = permits.clone();
+            queries.spawn(async move {
+                let _permit = permits.acquire_owned().await;
+                let answered = query_server(&address.0, address.1).await;
+                (address, answered)
+            });
+        }
+    }
+    while let Some(res) = queries.join_next().await {
+        if let Ok((address, answered)) = res {
+            reachable.insert(address, answered);
+        }
+    }
+
+    for bookmark in bookmarks {
+        let up = reachable.get(&(snapshot.address(&bookmark.host), bookmark.port)).copied().unwrap_or(false);
+        let name = format!("{}**{}**",
+            bookmark.emoji.as_ref().map(|emoji| format!("{emoji} ")).unwrap_or_default(),
+            bookmark.bookmark_name
+        );
+
+        if up {
+            misses.remove(&bookmark.id);
+            let (outage_id, down_for) = match open_outages.get(&bookmark.id) {
+                Some(outage) => *outage,
+                None => continue
+            };
+
+            let res = sqlx::query!("UPDATE server_outages SET ended_at = NOW() WHERE id = ?", outage_id)
+                .execute(database)
+                .await;
+            if let Err(e) = res {
+                println!("[ ERROR ] Ending outage for {} failed: {e}", bookmark.bookmark_name);
+                continue;
+            }
+
+            if let Some(channel_id) = bookmark.availability_channel {
+                let content = format!("✅ {} is back up after {} down.\n`/connect {} {}`",
+                    name,
+                    format_duration(down_for),
+                    bookmark.host,
+                    bookmark.port
+                );
+                notify(database, Notification {
+                    target: AlertTarget::Channel(channel_id),
+                    guild_id: Some(bookmark.guild_id),
+                    key: format!("outage:{}:up", outage_id),
+                    summary: format!("{} is back up", name),
+                    message: serenity::CreateMessage::new().content(content),
+                }).await;
+            }
+        } else {
+            let (missed, first_missed) = misses.entry(bookmark.id).or_insert((0, std::time::Instant::now()));
+            *missed += 1;
+            if *missed < OUTAGE_AFTER_MISSES || open_outages.contains_key(&bookmark.id) {
+                continue;
+            }
+
+            // The server was already down at the first miss
+            let down_for = first_missed.elapsed().as_secs();
+            let outage = match sqlx::query!("INSERT INTO server_outages (bookmark_id, started_at) VALUES (?, NOW() - INTERVAL ? SECOND)", bookmark.id, down_for)
+                .execute(database)
+                .await
+            {
+                Ok(outage) => outage,
+                Err(e) => {
+                    println!("[ ERROR ] Recording outage for {} failed: {e}", bookmark.bookmark_name);
+                    continue;
+                }
+            };
+
+            if let Some(channel_id) = bookmark.availability_channel {
+                let content = format!("❌ {} is down! It isn't listed on Sauertracker and doesn't answer queries.\n`/connect {} {}`",
+                    name,
+                    bookmark.host,
+                    bookmark.port
+                );
+                notify(database, Notification {
+                    target: AlertTarget::Channel(channel_id),
+                    guild_id: Some(bookmark.guild_id),
+                    key: format!("outage:{}:down", outage.last_insert_id()),
+                    summary: format!("{} is down", name),
+                    message: serenity::CreateMessage::new().content(content),
+                }).await;
+            }
+        }
+    }
+}
+
+// Ask a server for its info on port + 1 like the game's server browser does. Any reply
+// means it is up. Servers without a valid query port can't be asked.
+async fn query_server(host: &str, port: u32) -> bool {
+    let query_port = match u16::try_from(port).ok().and_then(|port| port.checked_add(1)) {
+        Some(query_port) => query_port,
+        None => return false
+    };
+
+    let socket = match tokio::net::UdpSocket::bind("0.0.0.0:0").await {
+        Ok(socket) => socket,
+        Err(_) => return false
+    };
+    if socket.connect((host, query_port)).await.is_err() {
+        return false;
+    }
+
+    let mut buf = [0u8; 512];
+    for _ in 0..QUERY_ATTEMPTS {
+        if socket.send(&[1]).await.is_err() {
+            return false;
+        }
+
+        if let Ok(Ok(len)) = tokio::time::timeout(QUERY_TIMEOUT, socket.recv(&mut buf)).await {
+            if len > 0 {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(day: u32, hour: u32) -> chrono::NaiveDateTime {
+        chrono::NaiveDate::from_ymd_opt(2026, 10, day).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn no_outages_is_fully_up() {
+        assert_eq!(uptime_percentage(&[], at(1, 0), at(2, 0)), Some(100.0));
+    }
+
+    #[test]
+    fn empty_range_has_no_data() {
+        assert_eq!(uptime_percentage(&[], at(1, 0), at(1, 0)), None);
+        assert_eq!(uptime_percentage(&[], at(2, 0), at(1, 0)), None);
+    }
+
+    #[test]
+    fn outages_inside_the_range_count_fully() {
+        let outages = [(at(1, 6), Some(at(1, 12)))];
+        assert_eq!(uptime_percentage(&outages, at(1, 0), at(2, 0)), Some(75.0));
+    }
+
+    #[test]
+    fn outages_are_clipped_to_the_range() {
+        // Started before the range and ended after it
+        let outages = [(at(1, 0), Some(at(3, 0)))];
+        assert_eq!(uptime_percentage(&outages, at(1, 12), at(2, 12)), Some(0.0));
+
+        // Only the last 6 hours fall in the range
+        let outages = [(at(1, 18), Some(at(2, 6)))];
+        assert_eq!(uptime_percentage(&outages, at(2, 0), at(3, 0)), Some(75.0));
+
+        // Entirely before the range
+        let outages = [(at(1, 0), Some(at(1, 6)))];
+        assert_eq!(uptime_percentage(&outages, at(2, 0), at(3, 0)), Some(100.0));
+    }
+
+    #[test]
+    fn ongoing_outages_run_until_the_end() {
+        let outages = [(at(1, 18), None)];
+        assert_eq!(uptime_percentage(&outages, at(1, 0), at(2, 0)), Some(75.0));
+    }
+
+    #[test]
+    fn overlapping_outages_never_go_below_zero() {
+        let outages = [(at(1, 0), Some(at(2, 0))), (at(1, 0), None)];
+        assert_eq!(uptime_percentage(&outages, at(1, 0), at(2, 0)), Some(0.0));
+    }
+}
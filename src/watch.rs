@@ -0,0 +1,575 @@
+use crate::{Context, Error};
+use crate::alerts::{alert_channel, AlertTarget};
+use crate::dispatch::{notify, Notification};
+use crate::bookmark::autocomplete_bookmark;
+use crate::admin::info_role;
+use crate::clan::{autocomplete_clantag, fetch_clan_members, fetch_clantags};
+use crate::data::{escape_markdown, has_clantag, plain_name, BasicServer, ServerBookmark};
+use crate::player::autocomplete_player;
+use crate::poller::Snapshot;
+use poise::serenity_prelude as serenity;
+use std::collections::HashMap;
+
+// Minutes before a server watch can alert again
+const SERVER_ALERT_COOLDOWN: u32 = 30;
+
+// Players a server must drop below its threshold before a watch alerts again
+const SERVER_REARM_MARGIN: u32 = 2;
+
+// Players watched at once by a user's DMs and by a guild
+const MAX_USER_PLAYER_WATCHES: i64 = 10;
+const MAX_GUILD_PLAYER_WATCHES: i64 = 25;
+
+// Polls in a row a watched player must be missing before they count as gone, so
+// a timeout or a quick reconnect to the same server doesn't alert again
+const PLAYER_LEAVE_POLLS: u32 = 3;
+
+// Clantagged players on one server when no minimum is given
+const DEFAULT_CLAN_MEMBERS: u32 = 3;
+
+// How long clan lists from the tracker are reused by clan watches
+const CLAN_CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(600);
+
+// Clan lists fetched for clan watches, with when they were fetched
+pub type ClanCache = HashMap<String, (std::time::Instant, Vec<String>)>;
+
+/// Post in this channel when a bookmarked server reaches a number of players
+#[poise::command(
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS",
+    guild_only
+)]
+pub async fn watchserver(
+    ctx: Context<'_>,
+    #[description = "Name of the server bookmark"]
+    #[autocomplete = "autocomplete_bookmark"] bookmark: String,
+    #[description = "Players needed on the server to post an alert"]
+    #[min = 1]
+    #[max = 128] min_players: u32,
+    #[description = "Role to ping with the alert"] role: Option<serenity::Role>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+
+    let bookmark_info = match sqlx::query_as!(ServerBookmark, "SELECT * FROM server_bookmarks WHERE guild_id = ? AND bookmark_name = ?", guild_id, bookmark)
+        .fetch_optional(&ctx.data().database)
+        .await
+        .unwrap()
+    {
+        Some(info) => info,
+        None => return Err("No bookmark with that name exists!".into())
+    };
+
+    let role_id = role.as_ref().map(|role| role.id.get());
+    sqlx::query!("INSERT INTO server_watches (guild_id, bookmark_id, channel_id, min_players, role_id) VALUES (?, ?, ?, ?, ?)
+        ON DUPLICATE KEY UPDATE channel_id = VALUES(channel_id), min_players = VALUES(min_players), role_id = VALUES(role_id), triggered = FALSE",
+        guild_id, bookmark_info.id, ctx.channel_id().get(), min_players, role_id)
+        .execute(&ctx.data().database)
+        .await
+        .unwrap();
+
+    let msg = format!("{}, alerts will be posted here when {} has {} or more players{}!",
+        ctx.author(),
+        bookmark,
+        min_players,
+        role.map(|role| format!(", pinging {}", role.name)).unwrap_or_default()
+    );
+    ctx.say(msg).await?;
+
+    Ok(())
+}
+
+/// Stop alerts for a bookmarked server's player count
+#[poise::command(
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS",
+    guild_only
+)]
+pub async fn unwatchserver(
+    ctx: Context<'_>,
+    #[description = "Name of the server bookmark"]
+    #[autocomplete = "autocomplete_bookmark"] bookmark: String,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+
+    let deleted = sqlx::query!("DELETE server_watches FROM server_watches JOIN server_bookmarks ON server_bookmarks.id = server_watches.bookmark_id WHERE server_watches.guild_id = ? AND server_bookmarks.bookmark_name = ?", guild_id, bookmark)
+        .execute(&ctx.data().database)
+        .await
+        .unwrap();
+
+    if deleted.rows_affected() == 0 {
+        return Err("That bookmark isn't being watched!".into());
+    }
+
+    let msg = format!("{}, stopped watching {}!", ctx.author(), bookmark);
+    ctx.say(msg).await?;
+
+    Ok(())
+}
+
+/// Get notified when a player shows up on any server
+#[poise::command(
+    slash_command,
+    check = "info_role",
+    user_cooldown = 10
+)]
+pub async fn watchplayer(
+    ctx: Context<'_>,
+    #[description = "Player's name"]
+    #[autocomplete = "autocomplete_player"]
+    #[max_length = 15] name: String,
+    #[description = "Post alerts in this channel instead of sending you a DM"]
+    #[channel_types("Text", "News")] channel: Option<serenity::GuildChannel>,
+) -> Result<(), Error> {
+    let channel_id = alert_channel(ctx, channel.as_ref()).await?;
+    let guild_id = channel.as_ref().map(|channel| channel.guild_id.get());
+    let user_id = ctx.author().id.get();
+
+    let existing = match guild_id {
+        Some(guild_id) => sqlx::query!("SELECT player_name FROM player_watches WHERE guild_id = ?", guild_id)
+            .fetch_all(&ctx.data().database)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|watch| watch.player_name)
+            .collect::<Vec<String>>(),
+        None => sqlx::query!("SELECT player_name FROM player_watches WHERE user_id = ? AND channel_id IS NULL", user_id)
+            .fetch_all(&ctx.data().database)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|watch| watch.player_name)
+            .collect::<Vec<String>>(),
+    };
+
+    let limit = if guild_id.is_some() { MAX_GUILD_PLAYER_WATCHES } else { MAX_USER_PLAYER_WATCHES };
+    if existing.len() as i64 >= limit {
+        return Err(format!("At most {} players can be watched at once!", limit).into());
+    }
+    if existing.iter().any(|watched| plain_name(watched) == plain_name(&name)) {
+        return Err("That player is already being watched!".into());
+    }
+
+    sqlx::query!("INSERT INTO player_watches (user_id, guild_id, channel_id, player_name) VALUES (?, ?, ?, ?)", user_id, guild_id, channel_id, name)
+        .execute(&ctx.data().database)
+        .await
+        .unwrap();
+
+    match &channel {
+        Some(channel) => {
+            ctx.say(format!("{}, alerts will be posted in {} when {} joins a server!", ctx.author(), channel, name)).await?;
+        }
+        None => {
+            ctx.send(poise::CreateReply::default()
+                .content(format!("{}, you'll get a DM when {} joins a server! Make sure the bot can send you DMs.", ctx.author(), name))
+                .ephemeral(true)
+            ).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Stop getting notified about a player
+#[poise::command(
+    slash_command,
+    check = "info_role",
+    user_cooldown = 10
+)]
+pub async fn unwatchplayer(
+    ctx: Context<'_>,
+    #[description = "Player's name"]
+    #[max_length = 15] name: String,
+    #[description = "Channel the alerts are posted in, or leave empty for your DMs"]
+    #[channel_types("Text", "News")] channel: Option<serenity::GuildChannel>,
+) -> Result<(), Error> {
+    // Watches match by name without colours, the same way /watchplayer checks for duplicates
+    let watches = match alert_channel(ctx, channel.as_ref()).await? {
+        Some(channel_id) => sqlx::query!("SELECT id, player_name FROM player_watches WHERE channel_id = ?", channel_id)
+            .fetch_all(&ctx.data().database)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|watch| (watch.id, watch.player_name))
+            .collect::<Vec<(i32, String)>>(),
+        None => sqlx::query!("SELECT id, player_name FROM player_watches WHERE user_id = ? AND channel_id IS NULL", ctx.author().id.get())
+            .fetch_all(&ctx.data().database)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|watch| (watch.id, watch.player_name))
+            .collect::<Vec<(i32, String)>>(),
+    };
+
+    let id = match watches.iter().find(|(_, watched)| plain_name(watched) == plain_name(&name)) {
+        Some((id, _)) => *id,
+        None => return Err("That player isn't being watched!".into())
+    };
+
+    sqlx::query!("DELETE FROM player_watches WHERE id = ?", id)
+        .execute(&ctx.data().database)
+        .await
+        .unwrap();
+
+    ctx.send(poise::CreateReply::default()
+        .content(format!("{}, stopped watching {}!", ctx.author(), name))
+        .ephemeral(channel.is_none())
+    ).await?;
+
+    Ok(())
+}
+
+/// Post in this channel when several players wearing a clantag are on one server
+#[poise::command(
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS",
+    guild_only
+)]
+pub async fn watchclan(
+    ctx: Context<'_>,
+    #[description = "Clantag, matched at the start or end of names"]
+    #[autocomplete = "autocomplete_clantag"]
+    #[max_length = 32] tag: String,
+    #[description = "Players wearing the tag needed on one server (default 3)"]
+    #[min = 2]
+    #[max = 32] min_members: Option<u32>,
+    #[description = "Only count players the tracker knows as clan members"] members_only: Option<bool>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let min_members = min_members.unwrap_or(DEFAULT_CLAN_MEMBERS);
+    let members_only = members_only.unwrap_or(false);
+
+    if members_only {
+        ctx.defer().await?;
+        fetch_clan_members(&ctx.data().client, &tag).await?;
+    }
+
+    sqlx::query!("INSERT INTO clan_watches (guild_id, clan_tag, channel_id, min_members, members_only) VALUES (?, ?, ?, ?, ?)
+        ON DUPLICATE KEY UPDATE channel_id = VALUES(channel_id), min_members = VALUES(min_members), members_only = VALUES(members_only), notified_server = NULL",
+        guild_id, tag, ctx.channel_id().get(), min_members, members_only)
+        .execute(&ctx.data().database)
+        .await
+        .unwrap();
+
+    let msg = format!("{}, alerts will be posted here when {} or more players wearing {} are on one server!", ctx.author(), min_members, tag);
+    ctx.say(msg).await?;
+
+    Ok(())
+}
+
+/// Stop alerts for a clantag
+#[poise::command(
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS",
+    guild_only
+)]
+pub async fn unwatchclan(
+    ctx: Context<'_>,
+    #[description = "Clantag being watched"]
+    #[max_length = 32] tag: String,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+
+    let deleted = sqlx::query!("DELETE FROM clan_watches WHERE guild_id = ? AND clan_tag = ?", guild_id, tag)
+        .execute(&ctx.data().database)
+        .await
+        .unwrap();
+
+    if deleted.rows_affected() == 0 {
+        return Err("That clantag isn't being watched!".into());
+    }
+
+    let msg = format!("{}, stopped watching {}!", ctx.author(), tag);
+    ctx.say(msg).await?;
+
+    Ok(())
+}
+
+// Alert once when a watched server reaches its threshold, then wait for it to
+// clearly drop below it again so a player leaving and rejoining doesn't alert twice
+pub async fn check_server_watches(database: &sqlx::MySqlPool, snapshot: &Snapshot) {
+    let watches = sqlx::query!("SELECT server_watches.guild_id, bookmark_id, channel_id, min_players, role_id, triggered AS `triggered: bool`,
+        (last_alert IS NOT NULL AND last_alert > NOW() - INTERVAL ? MINUTE) AS `cooling_down!: bool`,
+        bookmark_name, host, port, emoji
+        FROM server_watches JOIN server_bookmarks ON server_bookmarks.id = server_watches.bookmark_id", SERVER_ALERT_COOLDOWN)
+        .fetch_all(database)
+        .await
+        .unwrap_or_default();
+
+    for watch in watches {
+        let server = snapshot.server(&watch.host, watch.port);
+        let players = server.map(|server| server.clients as u32).unwrap_or(0);
+
+        if watch.triggered {
+            if players < watch.min_players.saturating_sub(SERVER_REARM_MARGIN).max(1) {
+                let res = sqlx::query!("UPDATE server_watches SET triggered = FALSE WHERE guild_id = ? AND bookmark_id = ?", watch.guild_id, watch.bookmark_id)
+                    .execute(database)
+                    .await;
+                if let Err(e) = res {
+                    println!("[ ERROR ] Rearming server watch for {} failed: {e}", watch.bookmark_name);
+                }
+            }
+            continue;
+        }
+
+        let server = match server {
+            Some(server) if players >= watch.min_players => server,
+            _ => continue
+        };
+
+        // Reaching the threshold again during the cooldown is skipped rather than delayed
+        if !watch.cooling_down {
+            let mut content = format!("{}{}**{}** has {}/{} players on {} {}!\n`/connect {} {}`",
+                watch.role_id.map(|id| format!("<@&{id}> ")).unwrap_or_default(),
+                watch.emoji.as_ref().map(|emoji| format!("{emoji} ")).unwrap_or_default(),
+                watch.bookmark_name,
+                server.clients,
+                server.maxClients,
+                server.gameMode,
+                server.mapName,
+                watch.host,
+                watch.port
+            );
+            if server.isFull {
+                content = format!("{content}\n*The server is full.*");
+            }
+
+            let mentions = serenity::CreateAllowedMentions::new()
+                .roles(watch.role_id.map(serenity::RoleId::new));
+            let queued = notify(database, Notification {
+                target: AlertTarget::Channel(watch.channel_id),
+                guild_id: Some(watch.guild_id),
+                key: format!("server:{}:{}", watch.host, watch.port),
+                summary: format!("{} has {}/{} players", watch.bookmark_name, server.clients, server.maxClients),
+                message: serenity::CreateMessage::new().content(content).allowed_mentions(mentions),
+            }).await;
+
+            // Stay armed so the next poll tries again
+            if !queued {
+                continue;
+            }
+        }
+
+        let res = sqlx::query!("UPDATE server_watches SET triggered = TRUE, last_alert = IF(?, last_alert, NOW()) WHERE guild_id = ? AND bookmark_id = ?", watch.cooling_down, watch.guild_id, watch.bookmark_id)
+            .execute(database)
+            .await;
+        if let Err(e) = res {
+            println!("[ ERROR ] Updating server watch for {} failed: {e}", watch.bookmark_name);
+        }
+    }
+}
+
+// Alert when a watched player is seen on a server other than the one last alerted for.
+// A player only counts as gone after missing PLAYER_LEAVE_POLLS polls in a row.
+pub async fn check_player_watches(database: &sqlx::MySqlPool, snapshot: &Snapshot) {
+    let watches = sqlx::query!("SELECT id, user_id, guild_id, channel_id, player_name, notified_server, missed_polls FROM player_watches")
+        .fetch_all(database)
+        .await
+        .unwrap_or_default();
+    if watches.is_empty() {
+        return;
+    }
+
+    let mut online: HashMap<String, (&String, &BasicServer)> = HashMap::new();
+    for server in &snapshot.servers {
+        for player in &server.players {
+            online.entry(plain_name(player)).or_insert((player, server));
+        }
+    }
+
+    for watch in watches {
+        let seen = online.get(&plain_name(&watch.player_name));
+        let address = seen.map(|(_, server)| format!("{}:{}", server.host, server.port));
+        if address == watch.notified_server {
+            if watch.missed_polls > 0 {
+                let res = sqlx::query!("UPDATE player_watches SET missed_polls = 0 WHERE id = ?", watch.id)
+                    .execute(database)
+                    .await;
+                if let Err(e) = res {
+                    println!("[ ERROR ] Updating player watch for {} failed: {e}", watch.player_name);
+                }
+            }
+            continue;
+        }
+
+        if seen.is_none() && watch.missed_polls + 1 < PLAYER_LEAVE_POLLS {
+            let res = sqlx::query!("UPDATE player_watches SET missed_polls = missed_polls + 1 WHERE id = ?", watch.id)
+                .execute(database)
+                .await;
+            if let Err(e) = res {
+                println!("[ ERROR ] Updating player watch for {} failed: {e}", watch.player_name);
+            }
+            continue;
+        }
+
+        if let Some((player, server)) = seen {
+            let embed = serenity::CreateEmbed::new()
+                .colour(0xFF0000)
+                .title(format!("{} is playing!", player))
+                .url(format!("https://sauertracker.net/server/{}/{}", server.host, server.port))
+                .description(format!("**Server:** {}\n**Players:** {}/{}\n*{} {}*",
+                    escape_markdown(server.description.clone()),
+                    server.clients,
+                    server.maxClients,
+                    server.mapName,
+                    server.gameMode
+                ))
+                .footer(serenity::CreateEmbedFooter::new(format!("/connect {} {}", server.host, server.port)));
+
+            let queued = notify(database, Notification {
+                target: AlertTarget::new(watch.channel_id, watch.user_id),
+                guild_id: watch.guild_id,
+                key: format!("player:{}:{}:{}", plain_name(player), server.host, server.port),
+                summary: format!("{} is playing on {}", escape_markdown(player.to_string()), escape_markdown(server.description.clone())),
+                message: serenity::CreateMessage::new().embed(embed),
+            }).await;
+            if !queued {
+                continue;
+            }
+        }
+
+        let res = sqlx::query!("UPDATE player_watches SET notified_server = ?, missed_polls = 0 WHERE id = ?", address, watch.id)
+            .execute(database)
+            .await;
+        if let Err(e) = res {
+            println!("[ ERROR ] Updating player watch for {} failed: {e}", watch.player_name);
+        }
+    }
+}
+
+// Alert when enough players wearing a watched clantag gather on one server. A server
+// is only alerted for once while the clan stays on it.
+pub async fn check_clan_watches(database: &sqlx::MySqlPool, client: &reqwest::Client, cache: &mut ClanCache, snapshot: &Snapshot) {
+    let watches = sqlx::query!("SELECT guild_id, clan_tag, channel_id, min_members, members_only AS `members_only: bool`, notified_server FROM clan_watches")
+        .fetch_all(database)
+        .await
+        .unwrap_or_default();
+    cache.retain(|_, (fetched_at, _)| fetched_at.elapsed() < CLAN_CACHE_TTL);
+
+    for watch in watches {
+        // Servers with enough tagged players, busiest first
+        let mut gatherings: Vec<(&BasicServer, Vec<&String>)> = snapshot.servers.iter()
+            .map(|server| (server, server.players.iter().filter(|name| has_clantag(name, &watch.clan_tag)).collect::<Vec<&String>>()))
+            .filter(|(_, tagged)| tagged.len() as u32 >= watch.min_members)
+            .collect();
+        gatherings.sort_by(|a, b| b.1.len().cmp(&a.1.len()));
+
+        let still_playing = gatherings.iter()
+            .any(|(server, _)| Some(format!("{}:{}", server.host, server.port)) == watch.notified_server);
+        if still_playing {
+            continue;
+        }
+
+        if gatherings.is_empty() {
+            if watch.notified_server.is_some() {
+                let res = sqlx::query!("UPDATE clan_watches SET notified_server = NULL WHERE guild_id = ? AND clan_tag = ?", watch.guild_id, watch.clan_tag)
+                    .execute(database)
+                    .await;
+                if let Err(e) = res {
+                    println!("[ ERROR ] Updating clan watch for {} failed: {e}", watch.clan_tag);
+                }
+            }
+            continue;
+        }
+
+        // Drop players the clan doesn't list as members
+        if watch.members_only {
+            let key = format!("clanmembers:{}", watch.clan_tag);
+            let members: Vec<String> = match cached_clan_list(cache, key, fetch_clan_members(client, &watch.clan_tag)).await {
+                Ok(members) => members.iter().map(|name| plain_name(name)).collect(),
+                Err(_) => continue
+            };
+
+            for (_, tagged) in gatherings.iter_mut() {
+                tagged.retain(|name| members.contains(&plain_name(name)));
+            }
+            gatherings.retain(|(_, tagged)| tagged.len() as u32 >= watch.min_members);
+            gatherings.sort_by(|a, b| b.1.len().cmp(&a.1.len()));
+        }
+
+        let (server, tagged) = match gatherings.first() {
+            Some(gathering) => gathering,
+            None => continue
+        };
+
+        let clantags = cached_clan_list(cache, String::from("clans"), fetch_clantags(client)).await.unwrap_or_default();
+        let opponents = opposing_tags(clantags, server, &watch.clan_tag);
+        let embed = serenity::CreateEmbed::new()
+            .colour(0xFF0000)
+            .title(format!("{} players wearing {} are on one server!", tagged.len(), watch.clan_tag))
+            .url(format!("https://sauertracker.net/server/{}/{}", server.host, server.port))
+            .description(format!("**Server:** {}\n**Players:** {}/{}\n*{} {}*\n\n**{}:** {}{}",
+                escape_markdown(server.description.clone()),
+                server.clients,
+                server.maxClients,
+                server.mapName,
+                server.gameMode,
+                watch.clan_tag,
+                tagged.iter().map(|name| escape_markdown(name.to_string())).collect::<Vec<String>>().join(", "),
+                if opponents.is_empty() {
+                    String::new()
+                } else {
+                    format!("\n**Against:** {}", opponents.join(", "))
+                }
+            ))
+            .footer(serenity::CreateEmbedFooter::new(format!("/connect {} {}", server.host, server.port)));
+
+        let queued = notify(database, Notification {
+            target: AlertTarget::Channel(watch.channel_id),
+            guild_id: Some(watch.guild_id),
+            key: format!("clan:{}:{}:{}", watch.clan_tag, server.host, server.port),
+            summary: format!("{} players wearing {} are on {}", tagged.len(), watch.clan_tag, escape_markdown(server.description.clone())),
+            message: serenity::CreateMessage::new().embed(embed),
+        }).await;
+        if !queued {
+            continue;
+        }
+
+        let res = sqlx::query!("UPDATE clan_watches SET notified_server = ? WHERE guild_id = ? AND clan_tag = ?", format!("{}:{}", server.host, server.port), watch.guild_id, watch.clan_tag)
+            .execute(database)
+            .await;
+        if let Err(e) = res {
+            println!("[ ERROR ] Updating clan watch for {} failed: {e}", watch.clan_tag);
+        }
+    }
+}
+
+// Reuse a clan list fetched in the last CLAN_CACHE_TTL so every poll doesn't ask the tracker again
+async fn cached_clan_list(
+    cache: &mut ClanCache,
+    key: String,
+    fetch: impl std::future::Future<Output = Result<Vec<String>, Error>>,
+) -> Result<Vec<String>, Error> {
+    if let Some((_, names)) = cache.get(&key) {
+        return Ok(names.clone());
+    }
+
+    let names = fetch.await?;
+    cache.insert(key, (std::time::Instant::now(), names.clone()));
+
+    Ok(names)
+}
+
+// Other known clantags worn on a server, with how many players wear each
+fn opposing_tags(mut clantags: Vec<String>, server: &BasicServer, clan_tag: &str) -> Vec<String> {
+    clantags.retain(|tag| !tag.eq_ignore_ascii_case(clan_tag));
+    // Prefer the longest tag a name matches, so "[ABC]" wins over "A"
+    clantags.sort_by(|a, b| b.len().cmp(&a.len()));
+
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    for name in server.players.iter().filter(|name| !has_clantag(name, clan_tag)) {
+        let tag = match clantags.iter().find(|tag| has_clantag(name, tag)) {
+            Some(tag) => tag,
+            None => continue
+        };
+
+        match counts.iter_mut().find(|(counted, _)| counted == tag) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((tag.clone(), 1))
+        }
+    }
+    counts.sort_by(|a, b| b.1.cmp(&a.1));
+
+    counts.into_iter()
+        .map(|(tag, count)| format!("{} ({})", escape_markdown(tag), count))
+        .collect()
+}